@@ -1,3 +1,6 @@
 pub mod world;
 pub mod search;
+pub mod plan;
+pub mod validator;
+pub mod puzzle;
 pub mod test;
\ No newline at end of file
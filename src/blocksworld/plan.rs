@@ -0,0 +1,102 @@
+use std::fmt;
+use std::slice;
+
+use blocksworld::search::Node;
+use blocksworld::world::{Direction, Entity, World, WorldError};
+
+// The ordered moves which take the agent from a start world to a goal world.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    moves: Vec<Direction>,
+}
+impl Plan {
+    pub fn new(moves: Vec<Direction>) -> Plan {
+        Plan { moves }
+    }
+    // Reads a plan written as move letters, e.g. "UULDR". Whitespace is ignored.
+    pub fn parse(text: &str) -> Result<Plan, PlanError> {
+        text.chars()
+            .enumerate()
+            .filter(|&(_, move_char)| !move_char.is_whitespace())
+            .map(|(position, move_char)| {
+                Direction::from_char(move_char).ok_or(PlanError::InvalidMoveError {
+                    position,
+                    move_char,
+                })
+            })
+            .collect::<Result<Vec<Direction>, PlanError>>()
+            .map(Plan::new)
+    }
+    // Follows a goal node's parents back to the root, recovering the move made between each pair of worlds.
+    pub fn from_node<N: Node>(goal_node: &N) -> Plan {
+        let mut moves = Vec::new();
+        let mut child_location = goal_node.get_world().get_entity_location(&Entity::Agent).unwrap();
+        let mut parent = goal_node.get_parent();
+        while let Some(node_rc) = parent {
+            let parent_location = node_rc.get_world().get_entity_location(&Entity::Agent).unwrap();
+            moves.push(parent_location.direction_to(&child_location).unwrap());
+            child_location = parent_location;
+            parent = node_rc.get_parent();
+        }
+        moves.reverse();
+        Plan { moves }
+    }
+    pub fn moves(&self) -> &[Direction] {
+        &self.moves
+    }
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+    // Every move costs the same, so a plan's cost is its length.
+    pub fn cost(&self) -> usize {
+        self.moves.len()
+    }
+    // Applies the plan's moves to the start world one at a time, yielding each resulting world.
+    pub fn replay<'a>(&'a self, start_world: &World) -> Replay<'a> {
+        Replay {
+            moves: self.moves.iter(),
+            world: Some(start_world.clone()),
+        }
+    }
+    // Whether replaying the plan from the start world ends at the goal world (wherever the agent is).
+    pub fn reaches_goal(&self, start_world: &World, goal_world: &World) -> Result<bool, WorldError> {
+        let mut world = start_world.clone();
+        for step in self.replay(start_world) {
+            world = step?;
+        }
+        Ok(world.eq_ignore_agent(goal_world))
+    }
+}
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for direction in &self.moves {
+            write!(f, "{}", direction.to_char())?;
+        }
+        Ok(())
+    }
+}
+
+// Iterator over the worlds visited while replaying a plan.
+// Stops after the first move which can't be made.
+pub struct Replay<'a> {
+    moves: slice::Iter<'a, Direction>,
+    world: Option<World>,
+}
+impl<'a> Iterator for Replay<'a> {
+    type Item = Result<World, WorldError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let direction = self.moves.next()?;
+        let world = self.world.take()?;
+        Some(world.clone_and_move_agent(direction).inspect(|new_world| {
+            self.world = Some(new_world.clone());
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub enum PlanError {
+    InvalidMoveError { position: usize, move_char: char },
+}
@@ -0,0 +1,379 @@
+use bidir_map::BidirMap;
+
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+use blocksworld::world::{Entity, Location, MoveRules, World, WorldError};
+
+// A start world and goal world, stored as text in the same bordered grid format as World::pretty_print:
+//
+// * * * * * *
+// *         *
+// * A B C @ *
+// * * * * * *
+//
+// The start grid comes first, followed by a blank line and the goal grid.
+// Each grid needs an agent ('@'), although the agent's location in the goal doesn't matter.
+// Walls ('*') inside the border are cells nothing can move into, and must match between the grids.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Puzzle {
+    pub start: World,
+    pub goal: World,
+}
+impl Puzzle {
+    pub fn new(start: World, goal: World) -> Puzzle {
+        Puzzle { start, goal }
+    }
+    // The same puzzle, with the agent moving blocks by the given rules in both worlds.
+    pub fn with_move_rules(self, move_rules: MoveRules) -> Puzzle {
+        Puzzle {
+            start: self.start.with_move_rules(move_rules),
+            goal: self.goal.with_move_rules(move_rules),
+        }
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Puzzle, PuzzleError> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        Puzzle::parse(&text)
+    }
+    pub fn parse(text: &str) -> Result<Puzzle, PuzzleError> {
+        // Grids are runs of non-blank lines, each paired with its line number.
+        let mut grids: Vec<Vec<(usize, &str)>> = Vec::new();
+        let mut in_grid = false;
+        for (line_index, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                in_grid = false;
+                continue;
+            }
+            if !in_grid {
+                grids.push(Vec::new());
+                in_grid = true;
+            }
+            grids.last_mut().unwrap().push((line_index + 1, line));
+        }
+        if grids.len() != 2 {
+            return Err(PuzzleError::GridCountError { found: grids.len() });
+        }
+
+        let start = parse_grid(&grids[0])?;
+        let goal = parse_grid(&grids[1])?;
+        let start_blocks = start.blocks().map(|(block_char, _)| block_char);
+        if start.width() != goal.width() || start.height() != goal.height() ||
+           !start_blocks.eq(goal.blocks().map(|(block_char, _)| block_char)) ||
+           !start.walls().eq(goal.walls()) {
+            return Err(PuzzleError::MismatchedGridsError { line: grids[1][0].0 });
+        }
+
+        Ok(Puzzle::new(start, goal))
+    }
+}
+impl fmt::Display for Puzzle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\n{}", self.start, self.goal)
+    }
+}
+
+// Lines and columns are counted from 1.
+#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum PuzzleError {
+    IoError(io::Error),
+    GridCountError { found: usize },
+    MalformedGridError { line: usize },
+    MissingWallError { line: usize, column: usize },
+    RaggedRowError { line: usize, expected_length: usize, found_length: usize },
+    UnexpectedCharacterError { line: usize, column: usize, found: char },
+    DuplicateAgentError { line: usize, column: usize },
+    DuplicateBlockError { line: usize, column: usize, block_char: char },
+    MissingAgentError { line: usize },
+    MismatchedGridsError { line: usize },
+    InvalidWorldError { line: usize, error: WorldError },
+}
+impl From<io::Error> for PuzzleError {
+    fn from(error: io::Error) -> PuzzleError {
+        PuzzleError::IoError(error)
+    }
+}
+impl fmt::Display for PuzzleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PuzzleError::IoError(ref error) => write!(f, "could not read puzzle: {}", error),
+            PuzzleError::GridCountError { found } => {
+                write!(f, "expected a start grid and a goal grid, found {} grids", found)
+            }
+            PuzzleError::MalformedGridError { line } => {
+                write!(f, "line {}: grid must be at least 1x1 and surrounded by walls", line)
+            }
+            PuzzleError::MissingWallError { line, column } => {
+                write!(f, "line {}, column {}: expected a wall ('*')", line, column)
+            }
+            PuzzleError::RaggedRowError { line, expected_length, found_length } => {
+                write!(f,
+                       "line {}: row is {} characters long, expected {}",
+                       line,
+                       found_length,
+                       expected_length)
+            }
+            PuzzleError::UnexpectedCharacterError { line, column, found } => {
+                write!(f, "line {}, column {}: unexpected character '{}'", line, column, found)
+            }
+            PuzzleError::DuplicateAgentError { line, column } => {
+                write!(f, "line {}, column {}: grid already has an agent", line, column)
+            }
+            PuzzleError::DuplicateBlockError { line, column, block_char } => {
+                write!(f,
+                       "line {}, column {}: grid already has a block '{}'",
+                       line,
+                       column,
+                       block_char)
+            }
+            PuzzleError::MissingAgentError { line } => {
+                write!(f, "line {}: grid has no agent ('@')", line)
+            }
+            PuzzleError::MismatchedGridsError { line } => {
+                write!(f,
+                       "line {}: goal grid must be the same size and have the same blocks and \
+                        walls as the start grid",
+                       line)
+            }
+            PuzzleError::InvalidWorldError { line, ref error } => {
+                write!(f, "line {}: invalid grid: {:?}", line, error)
+            }
+        }
+    }
+}
+
+fn parse_grid(lines: &[(usize, &str)]) -> Result<World, PuzzleError> {
+    let (first_line, top_wall) = lines[0];
+    // A grid of width w is drawn as w + 2 walls or cells, each followed by padding except the last.
+    let line_length = top_wall.chars().count();
+    if line_length < 5 || line_length % 2 == 0 || lines.len() < 3 {
+        return Err(PuzzleError::MalformedGridError { line: first_line });
+    }
+    let width = (line_length - 3) / 2;
+    let height = lines.len() - 2;
+
+    let mut entities = BidirMap::new();
+    let mut walls = Vec::new();
+    for (y, &(line, row)) in lines.iter().enumerate() {
+        let row = row.chars().collect::<Vec<char>>();
+        if row.len() != line_length {
+            return Err(PuzzleError::RaggedRowError {
+                line,
+                expected_length: line_length,
+                found_length: row.len(),
+            });
+        }
+        let is_wall_row = y == 0 || y == height + 1;
+        for (column, &grid_char) in row.iter().enumerate() {
+            let is_padding = column % 2 == 1;
+            let is_wall = is_wall_row || column == 0 || column == line_length - 1;
+            if is_padding {
+                if grid_char != ' ' {
+                    return Err(PuzzleError::UnexpectedCharacterError {
+                        line,
+                        column: column + 1,
+                        found: grid_char,
+                    });
+                }
+                continue;
+            }
+            if is_wall {
+                if grid_char != '*' {
+                    return Err(PuzzleError::MissingWallError {
+                        line,
+                        column: column + 1,
+                    });
+                }
+                continue;
+            }
+
+            let location = Location::new(column as isize / 2 - 1, y as isize - 1);
+            let entity = match grid_char {
+                ' ' => continue,
+                '*' => {
+                    walls.push(location);
+                    continue;
+                }
+                '@' => Entity::Agent,
+                block_char if block_char.is_alphabetic() => Entity::Block(block_char),
+                found => {
+                    return Err(PuzzleError::UnexpectedCharacterError {
+                        line,
+                        column: column + 1,
+                        found,
+                    })
+                }
+            };
+            if entities.contains_first_key(&entity) {
+                return Err(match entity {
+                    Entity::Block(block_char) => {
+                        PuzzleError::DuplicateBlockError {
+                            line,
+                            column: column + 1,
+                            block_char,
+                        }
+                    }
+                    _ => {
+                        PuzzleError::DuplicateAgentError {
+                            line,
+                            column: column + 1,
+                        }
+                    }
+                });
+            }
+            entities.insert(entity, location);
+        }
+    }
+    if !entities.contains_first_key(&Entity::Agent) {
+        return Err(PuzzleError::MissingAgentError { line: first_line });
+    }
+
+    World::new(width, height, &entities)
+        .and_then(|world| world.with_walls(&walls))
+        .map_err(|error| {
+            PuzzleError::InvalidWorldError {
+                line: first_line,
+                error,
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Puzzle, PuzzleError};
+    use blocksworld::world::WorldError;
+
+    const START: &str = "\
+* * * * *
+* A   @ *
+* * * * *";
+    const GOAL: &str = "\
+* * * * *
+*   A @ *
+* * * * *";
+
+    // The start grid on lines 1-3 and the goal grid on lines 5-7, with one line replaced.
+    fn parse_with_line(line: usize, replacement: &str) -> PuzzleError {
+        let text = format!("{}\n\n{}\n", START, GOAL);
+        let lines = text.lines()
+            .enumerate()
+            .map(|(index, text)| if index + 1 == line { replacement } else { text })
+            .collect::<Vec<&str>>();
+        Puzzle::parse(&lines.join("\n")).unwrap_err()
+    }
+
+    #[test]
+    fn round_trips_through_display() {
+        for text in [include_str!("../../puzzles/readme.txt"),
+                     include_str!("../../puzzles/warehouse_6x5.txt")]
+            .iter() {
+            let puzzle = Puzzle::parse(text).unwrap();
+            assert_eq!(Puzzle::parse(&puzzle.to_string()).unwrap(), puzzle);
+        }
+    }
+
+    #[test]
+    fn reports_missing_file() {
+        match Puzzle::load("puzzles/no_such_puzzle.txt") {
+            Err(PuzzleError::IoError(_)) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn reports_grid_count() {
+        match Puzzle::parse(START).unwrap_err() {
+            PuzzleError::GridCountError { found: 1 } => {}
+            error => panic!("unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn reports_malformed_grid() {
+        // A grid needs a row between its top and bottom walls.
+        match Puzzle::parse(&format!("{}\n\n* * * * *\n* * * * *\n", START)).unwrap_err() {
+            PuzzleError::MalformedGridError { line: 5 } => {}
+            error => panic!("unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn reports_missing_wall() {
+        match parse_with_line(2, "* A   @ A") {
+            PuzzleError::MissingWallError { line: 2, column: 9 } => {}
+            error => panic!("unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn reports_ragged_row() {
+        match parse_with_line(6, "*   A @   *") {
+            PuzzleError::RaggedRowError { line: 6, expected_length: 9, found_length: 11 } => {}
+            error => panic!("unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn reports_unexpected_character() {
+        match parse_with_line(2, "* A - @ *") {
+            PuzzleError::UnexpectedCharacterError { line: 2, column: 5, found: '-' } => {}
+            error => panic!("unexpected error: {:?}", error),
+        }
+        match parse_with_line(6, "*   A.@ *") {
+            PuzzleError::UnexpectedCharacterError { line: 6, column: 6, found: '.' } => {}
+            error => panic!("unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn reports_duplicate_agent() {
+        match parse_with_line(2, "* @   @ *") {
+            PuzzleError::DuplicateAgentError { line: 2, column: 7 } => {}
+            error => panic!("unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn reports_duplicate_block() {
+        match parse_with_line(6, "* A A @ *") {
+            PuzzleError::DuplicateBlockError { line: 6, column: 5, block_char: 'A' } => {}
+            error => panic!("unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn reports_missing_agent() {
+        match parse_with_line(6, "*   A   *") {
+            PuzzleError::MissingAgentError { line: 5 } => {}
+            error => panic!("unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn reports_mismatched_grids() {
+        match parse_with_line(6, "*   B @ *") {
+            PuzzleError::MismatchedGridsError { line: 5 } => {}
+            error => panic!("unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn reports_invalid_world() {
+        // 257x256 cells is one row more than a World can hold.
+        let wall_row = vec!["*"; 259].join(" ");
+        let empty_row = format!("*{}*", " ".repeat(2 * 257 + 1));
+        let mut rows = vec![wall_row.clone(), empty_row.replacen("  ", " @", 1)];
+        rows.extend((1..256).map(|_| empty_row.clone()));
+        rows.push(wall_row);
+        let grid = rows.join("\n");
+        match Puzzle::parse(&format!("{}\n\n{}\n", grid, grid)).unwrap_err() {
+            PuzzleError::InvalidWorldError { line: 1, error: WorldError::GridTooLargeError } => {}
+            error => panic!("unexpected error: {:?}", error),
+        }
+    }
+}
@@ -1,47 +1,82 @@
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, HashMap};
+use std::collections::hash_map::Entry;
 use std::cmp::Ordering;
 use std::rc::Rc;
 
+use super::{Heuristic, ManhattanHeuristic};
 use super::Node;
+use super::{PruneReason, SearchEvent};
 use super::Searcher;
+use super::SearchOptions;
+use super::SearchStats;
 use super::SearcherError;
 use ::blocksworld::world;
 
+// The open list is a binary heap with lazy deletion: rather than searching the heap for a state's
+// existing entry when a cheaper path to it is found, the cheaper node is pushed and the best cost
+// to each state is recorded. Entries costing more than the recorded best are skipped when popped.
+// The explored set acts as the closed list, so A* always performs a graph search.
+//
+// With a weight above 1 this is weighted A*, ordering nodes by f(n) = g(n) + w * h(n).
+// Nodes which look close to the goal are expanded sooner, so far fewer nodes are expanded,
+// but the plan found can be up to w times longer than optimal.
 pub struct AStarSearcher {
     start_world: world::World,
     goal_world: world::World,
     fringe: BinaryHeap<AStarNode>,
+    best_costs: HashMap<world::WorldState, usize>,
+    options: SearchOptions,
+    explored: HashMap<world::WorldState, u32>,
+    heuristic: Box<dyn Heuristic>,
+    weight: f64,
+    cost_bound: Option<usize>,
+    // Nodes dropped because a path to their world state at least as cheap was already in the fringe.
+    fringe_duplicates: u32,
 }
 impl AStarSearcher {
     pub fn new(start_world: world::World, goal_world: world::World) -> AStarSearcher {
         AStarSearcher {
-            start_world: start_world,
-            goal_world: goal_world,
+            start_world,
+            goal_world,
             fringe: BinaryHeap::new(),
+            best_costs: HashMap::new(),
+            options: SearchOptions::default(),
+            explored: HashMap::new(),
+            heuristic: Box::new(ManhattanHeuristic),
+            weight: 1.0,
+            cost_bound: None,
+            fringe_duplicates: 0,
         }
     }
-    pub fn search(&mut self) -> Result<(AStarNode, u32), (SearcherError, u32)> {
-        Searcher::search(self, None)
+    pub fn with_options(mut self, options: SearchOptions) -> AStarSearcher {
+        self.options = options;
+        self
     }
-
-    // Calculates manhattan distance between given world and goal world for each block.
-    fn heuristic(&self, world: &world::World) -> usize {
-        world.entities
-            .iter()
-            .filter(|&&(ref ent, _)| *ent != world::Entity::Agent)
-            .map(|&(ref ent, ref loc)| {
-                loc.distance_to(self.get_goal_world().get_entity_location(&ent).unwrap())
-            })
-            .sum::<usize>()
+    // Plans are only optimal with an admissible heuristic, which all of the built in ones are.
+    pub fn with_heuristic<H: Heuristic + 'static>(mut self, heuristic: H) -> AStarSearcher {
+        self.heuristic = Box::new(heuristic);
+        self
+    }
+    // Multiplies the heuristic by the weight. Weights below 1 are treated as 1.
+    pub fn with_weight(mut self, weight: f64) -> AStarSearcher {
+        self.weight = weight.max(1.0);
+        self
+    }
+    // Only looks for plans cheaper than this, pruning nodes whose f(n) (without the weight) reaches it.
+    // With an admissible heuristic, failing to find the goal then proves no cheaper plan exists.
+    pub fn with_cost_bound(mut self, cost_bound: usize) -> AStarSearcher {
+        self.cost_bound = Some(cost_bound);
+        self
+    }
+    pub fn search(&mut self) -> Result<(AStarNode, SearchStats), (SearcherError, SearchStats)> {
+        Searcher::search(self)
     }
 
-    // If a node's world state is already in the fringe,
-    // and the node containing it has a higher priority, don't add this new node.
-    fn is_node_unoptimal(&self, node: &AStarNode) -> bool {
-        self.fringe
-            .iter()
-            .filter(|n| *n.get_world() == *node.get_world())
-            .any(|n| node.start_to_self_cost >= n.start_to_self_cost)
+    // A node is stale if a cheaper path to its world state has been found since it was pushed.
+    fn is_node_stale(&self, node: &AStarNode) -> bool {
+        self.best_costs
+            .get(node.get_world().state())
+            .is_some_and(|&best_cost| best_cost < node.start_to_self_cost)
     }
 }
 impl Searcher for AStarSearcher {
@@ -52,83 +87,202 @@ impl Searcher for AStarSearcher {
     fn get_goal_world(&self) -> &world::World {
         &self.goal_world
     }
+    // The weight, so 1 for plain A*.
+    fn suboptimality_bound(&self) -> Option<f64> {
+        Some(self.weight)
+    }
+    fn get_options(&self) -> &SearchOptions {
+        &self.options
+    }
+    // A* always uses the explored set as its closed list, whatever the options say.
+    fn explored_states(&mut self) -> Option<&mut HashMap<world::WorldState, u32>> {
+        Some(&mut self.explored)
+    }
     fn fringe_push(&mut self, node: Self::NodeType) {
-        if self.is_node_unoptimal(&node) {
+        if self.cost_bound.is_some_and(|cost_bound| node.get_total_cost() >= cost_bound) {
+            self.options.notify(SearchEvent::Pruned {
+                world: node.get_world(),
+                depth: node.get_depth(),
+                reason: PruneReason::CostBound,
+            });
             return;
         }
+        // Only keep the node if it is the cheapest path to its world state found so far.
+        match self.best_costs.entry(node.get_world().state().clone()) {
+            Entry::Occupied(ref entry) if *entry.get() <= node.start_to_self_cost => {
+                self.fringe_duplicates += 1;
+                self.options.notify(SearchEvent::Pruned {
+                    world: node.get_world(),
+                    depth: node.get_depth(),
+                    reason: PruneReason::Duplicate,
+                });
+                return;
+            }
+            Entry::Occupied(mut entry) => {
+                entry.insert(node.start_to_self_cost);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(node.start_to_self_cost);
+            }
+        }
         self.fringe.push(node);
     }
     fn fringe_pop(&mut self) -> Option<Self::NodeType> {
-        self.fringe.pop()
+        while let Some(node) = self.fringe.pop() {
+            if !self.is_node_stale(&node) {
+                return Some(node);
+            }
+            self.fringe_duplicates += 1;
+            self.options.notify(SearchEvent::Pruned {
+                world: node.get_world(),
+                depth: node.get_depth(),
+                reason: PruneReason::Duplicate,
+            });
+        }
+        None
+    }
+    fn fringe_duplicates(&self) -> u32 {
+        self.fringe_duplicates
+    }
+    fn fringe_len(&self) -> usize {
+        self.fringe.len()
+    }
+    fn fringe_clear(&mut self) {
+        self.fringe.clear();
+        self.best_costs.clear();
+        self.fringe_duplicates = 0;
     }
     fn new_node(&self,
                 depth: u32,
-                world: Box<world::World>,
+                world: world::World,
                 parent: Option<Rc<Self::NodeType>>)
                 -> Self::NodeType {
-        let heuristic = self.heuristic(&*world);
-        let start_to_self_cost = match &parent { // Each node is only 1 move away from its parent.
-            &Some(ref parent_rc) => parent_rc.start_to_self_cost + 1,
-            &None => 0,
+        let heuristic = self.heuristic.estimate(&world, self.get_goal_world());
+        let start_to_self_cost = match parent { // Each node is only 1 move away from its parent.
+            Some(ref parent_rc) => parent_rc.start_to_self_cost + 1,
+            None => 0,
         };
-        AStarNode::new(depth, world, parent, start_to_self_cost, heuristic)
+        AStarNode::new(depth, world, parent, start_to_self_cost, heuristic).with_weight(self.weight)
     }
 }
 
 #[derive(Clone)]
 pub struct AStarNode {
     depth: u32,
-    world: Box<world::World>,
+    world: world::World,
     parent: Option<Rc<AStarNode>>,
     start_to_self_cost: usize,
     heuristic: usize,
+    // The heuristic multiplied by weighted A*'s weight, which orders the priority queue.
+    weighted_heuristic: usize,
 }
 impl AStarNode {
-    fn new(depth: u32,
-           world: Box<world::World>,
-           parent: Option<Rc<Self>>,
-           start_to_self_cost: usize,
-           heuristic: usize)
-           -> Self {
+    pub(super) fn new(depth: u32,
+                      world: world::World,
+                      parent: Option<Rc<Self>>,
+                      start_to_self_cost: usize,
+                      heuristic: usize)
+                      -> Self {
         AStarNode {
-            depth: depth,
-            world: world,
-            parent: parent,
-            start_to_self_cost: start_to_self_cost,
-            heuristic: heuristic,
+            depth,
+            world,
+            parent,
+            start_to_self_cost,
+            heuristic,
+            weighted_heuristic: heuristic,
         }
     }
+    // Rounding the weighted estimate down keeps within weighted A*'s bound.
+    pub(super) fn with_weight(mut self, weight: f64) -> Self {
+        self.weighted_heuristic = (self.heuristic as f64 * weight).floor() as usize;
+        self
+    }
+    pub fn get_start_to_self_cost(&self) -> usize {
+        self.start_to_self_cost
+    }
+    pub fn get_heuristic(&self) -> usize {
+        self.heuristic
+    }
+    // f(n), the estimated cost of the cheapest path to the goal through this node.
+    pub fn get_total_cost(&self) -> usize {
+        self.start_to_self_cost + self.heuristic
+    }
 }
 impl Node for AStarNode {
     fn get_world(&self) -> &world::World {
-        &*self.world
+        &self.world
     }
     fn get_depth(&self) -> u32 {
         self.depth
     }
     fn get_parent(&self) -> Option<Rc<Self>> {
-        match &self.parent {
-            &Some(ref parent_rc) => Some(parent_rc.clone()),
-            &None => None,
+        self.parent.clone()
+    }
+}
+impl Drop for AStarNode {
+    // See BasicNode's drop.
+    fn drop(&mut self) {
+        let mut parent = self.parent.take();
+        while let Some(Ok(mut node)) = parent.map(Rc::try_unwrap) {
+            parent = node.parent.take();
         }
     }
 }
 
 // The below code sets up ordering so that the priority queue will order nodes by their f(n), minimum at the top.
+// Ties are broken in favour of the deeper node, as it is likely closer to the goal.
 impl PartialEq for AStarNode {
     fn eq(&self, other: &AStarNode) -> bool {
-        (self.start_to_self_cost + self.heuristic) == (other.start_to_self_cost + other.heuristic)
+        self.cmp(other) == Ordering::Equal
     }
 }
 impl Eq for AStarNode {}
 impl PartialOrd for AStarNode {
     fn partial_cmp(&self, other: &AStarNode) -> Option<Ordering> {
-        (other.start_to_self_cost + other.heuristic)
-            .partial_cmp(&(self.start_to_self_cost + self.heuristic))
+        Some(self.cmp(other))
     }
 }
 impl Ord for AStarNode {
     fn cmp(&self, other: &AStarNode) -> Ordering {
-        other.partial_cmp(self).unwrap()
+        (other.start_to_self_cost + other.weighted_heuristic)
+            .cmp(&(self.start_to_self_cost + self.weighted_heuristic))
+            .then(self.start_to_self_cost.cmp(&other.start_to_self_cost))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::AStarSearcher;
+    use blocksworld::puzzle::Puzzle;
+    use blocksworld::search::{DirectionOrder, Node, SearchOptions, Searcher};
+    use blocksworld::test::fixtures::{test_puzzles, TestPuzzle};
+
+    #[test]
+    fn finds_optimal_plans() {
+        for TestPuzzle { name, puzzle, optimal_length } in test_puzzles() {
+            let move_rules = puzzle.goal.move_rules();
+            let (goal_node, _) = AStarSearcher::new(puzzle.start, puzzle.goal).search().unwrap();
+            assert_eq!(goal_node.get_depth(),
+                       optimal_length,
+                       "{} under {} rules",
+                       name,
+                       move_rules.name());
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn searches_again_from_scratch() {
+        let puzzle = Puzzle::parse(include_str!("../../../puzzles/readme.txt")).unwrap();
+        let mut searcher = AStarSearcher::new(puzzle.start, puzzle.goal)
+            .with_options(SearchOptions {
+                direction_order: DirectionOrder::Fixed,
+                ..SearchOptions::default()
+            });
+        // Through the trait, which doesn't go through the inherent search.
+        let (first_goal_node, first_stats) = Searcher::search(&mut searcher).unwrap();
+        let (second_goal_node, second_stats) = Searcher::search(&mut searcher).unwrap();
+        assert_eq!(second_goal_node.get_depth(), first_goal_node.get_depth());
+        assert_eq!(second_stats.expanded_nodes, first_stats.expanded_nodes);
+        assert_eq!(second_stats.generated_nodes, first_stats.generated_nodes);
+        assert_eq!(second_stats.duplicates_pruned, first_stats.duplicates_pruned);
+    }
+}
@@ -0,0 +1,117 @@
+use std::rc::Rc;
+use std::time::Instant;
+
+use super::AStarNode;
+use super::AStarSearcher;
+use super::Node;
+use super::{Heuristic, ManhattanHeuristic};
+use super::SearchOptions;
+use super::SearchStats;
+use super::SearcherError;
+use ::blocksworld::world;
+
+// Anytime repairing A*: finds a plan quickly with a heavily weighted A*, then keeps searching with
+// smaller and smaller weights for shorter plans, passing each one to a callback as it is found.
+// Each search prunes nodes which can't lead to a plan shorter than the best so far,
+// so a search which finds nothing proves the best plan so far is optimal, as does one with a weight of 1.
+// The timeout, node budget and cancel flag cover every search together; hitting one of them just stops
+// improving the plan, unless no plan has been found yet.
+pub struct AnytimeSearcher {
+    start_world: world::World,
+    goal_world: world::World,
+    options: SearchOptions,
+    heuristic: Rc<dyn Heuristic>,
+    initial_weight: f64,
+    weight_step: f64,
+}
+impl AnytimeSearcher {
+    pub const DEFAULT_INITIAL_WEIGHT: f64 = 3.0;
+    pub const DEFAULT_WEIGHT_STEP: f64 = 0.5;
+
+    pub fn new(start_world: world::World, goal_world: world::World) -> AnytimeSearcher {
+        AnytimeSearcher {
+            start_world,
+            goal_world,
+            options: SearchOptions::default(),
+            heuristic: Rc::new(ManhattanHeuristic),
+            initial_weight: AnytimeSearcher::DEFAULT_INITIAL_WEIGHT,
+            weight_step: AnytimeSearcher::DEFAULT_WEIGHT_STEP,
+        }
+    }
+    pub fn with_options(mut self, options: SearchOptions) -> AnytimeSearcher {
+        self.options = options;
+        self
+    }
+    // The bounds are only correct with an admissible heuristic.
+    pub fn with_heuristic<H: Heuristic + 'static>(mut self, heuristic: H) -> AnytimeSearcher {
+        self.heuristic = Rc::new(heuristic);
+        self
+    }
+    // The weight of the first search, and how much it is lowered by for each search after that.
+    pub fn with_weights(mut self, initial_weight: f64, weight_step: f64) -> AnytimeSearcher {
+        self.initial_weight = initial_weight.max(1.0);
+        self.weight_step = weight_step;
+        self
+    }
+    // Calls on_improvement with each plan shorter than those before it, along with its suboptimality bound.
+    // Returns the best plan found, with the stats of every search added up. The stats'
+    // suboptimality bound is the weight the best plan was found at, or 1 once it's proven optimal.
+    pub fn search<F>(&mut self,
+                     mut on_improvement: F)
+                     -> Result<(AStarNode, SearchStats), (SearcherError, SearchStats)>
+        where F: FnMut(&AStarNode, f64)
+    {
+        let mut bound = None;
+        let started = Instant::now();
+        let mut best_node: Option<AStarNode> = None;
+        let mut stats = SearchStats::default();
+        let mut weight = self.initial_weight;
+        let error = loop {
+            let options = self.options.remaining(started, &stats);
+
+            let mut a_star_searcher = AStarSearcher::new(self.start_world.clone(),
+                                                         self.goal_world.clone())
+                .with_options(options)
+                .with_heuristic(self.heuristic.clone())
+                .with_weight(weight);
+            // Only look for plans shorter than the best so far.
+            if let Some(ref best_node) = best_node {
+                a_star_searcher = a_star_searcher.with_cost_bound(best_node.get_start_to_self_cost());
+            }
+            let result = a_star_searcher.search();
+            match result {
+                Ok((node, run_stats)) => {
+                    stats.add(&run_stats);
+                    let is_optimal = weight <= 1.0 || node.get_start_to_self_cost() == 0;
+                    let plan_bound = if is_optimal { 1.0 } else { weight };
+                    on_improvement(&node, plan_bound);
+                    bound = Some(plan_bound);
+                    best_node = Some(node);
+                    if is_optimal {
+                        break None;
+                    }
+                }
+                Err((SearcherError::GoalNotFoundError, run_stats)) if best_node.is_some() => {
+                    // Every shorter plan was searched for, so the best so far is optimal.
+                    stats.add(&run_stats);
+                    bound = Some(1.0);
+                    break None;
+                }
+                Err((error, run_stats)) => {
+                    stats.add(&run_stats);
+                    break Some(error);
+                }
+            }
+            weight = (weight - self.weight_step).max(1.0);
+        };
+        stats.elapsed = started.elapsed();
+        match best_node {
+            Some(best_node) => {
+                stats.solution_depth = Some(best_node.get_depth());
+                stats.suboptimality_bound = bound;
+                Ok((best_node, stats))
+            }
+            None => Err((error.unwrap(), stats)),
+        }
+    }
+}
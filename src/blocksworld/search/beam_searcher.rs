@@ -0,0 +1,209 @@
+use std::collections::HashSet;
+use std::mem;
+use std::rc::Rc;
+use std::time::Instant;
+
+use super::AStarNode;
+use super::DeadlockDetector;
+use super::Directions;
+use super::Node;
+use super::{PruneReason, SearchEvent};
+use super::{Heuristic, ManhattanHeuristic};
+use super::SearchLimits;
+use super::SearchOptions;
+use super::state_size;
+use super::SearchStats;
+use super::SearcherError;
+use ::blocksworld::world;
+
+// Breadth first search which only keeps the best nodes of each depth, by heuristic, so memory use
+// stays proportional to the beam width times the depth however large the grid is.
+// The beam can drop every path to the goal, so it isn't complete, and there's no bound on how much
+// longer than optimal its plans can be.
+// Worlds which have been in the beam are never added again, so it can't loop and eventually empties.
+//
+// With widening, a search which empties its beam (or reaches the max depth) after dropping nodes is
+// restarted with twice the width, until the goal is found or the widest allowed beam fails too.
+pub struct BeamSearcher {
+    start_world: world::World,
+    goal_world: world::World,
+    options: SearchOptions,
+    heuristic: Box<dyn Heuristic>,
+    width: usize,
+    // The widest beam to widen to, if widening.
+    max_width: Option<usize>,
+    // The width of each search of the last run, and the number of nodes it expanded.
+    widths: Vec<(usize, u32)>,
+}
+impl BeamSearcher {
+    pub const DEFAULT_WIDTH: usize = 64;
+
+    pub fn new(start_world: world::World, goal_world: world::World) -> BeamSearcher {
+        BeamSearcher {
+            start_world,
+            goal_world,
+            options: SearchOptions::default(),
+            heuristic: Box::new(ManhattanHeuristic),
+            width: BeamSearcher::DEFAULT_WIDTH,
+            max_width: None,
+            widths: Vec::new(),
+        }
+    }
+    pub fn with_options(mut self, options: SearchOptions) -> BeamSearcher {
+        self.options = options;
+        self
+    }
+    // The heuristic doesn't need to be admissible, as the plans aren't optimal anyway.
+    pub fn with_heuristic<H: Heuristic + 'static>(mut self, heuristic: H) -> BeamSearcher {
+        self.heuristic = Box::new(heuristic);
+        self
+    }
+    // The number of nodes kept at each depth.
+    pub fn with_width(mut self, width: usize) -> BeamSearcher {
+        self.width = width.max(1);
+        self
+    }
+    // Restarts failed searches with double the width, up to this width. Without it, a failed
+    // search isn't retried.
+    pub fn with_widening(mut self, max_width: usize) -> BeamSearcher {
+        self.max_width = Some(max_width);
+        self
+    }
+    pub fn widths(&self) -> &[(usize, u32)] {
+        &self.widths
+    }
+    pub fn search(&mut self) -> Result<(AStarNode, SearchStats), (SearcherError, SearchStats)> {
+        self.widths.clear();
+        let started = Instant::now();
+        let mut stats = SearchStats::default();
+        let mut width = self.width;
+        let max_width = self.max_width.map_or(width, |max_width| max_width.max(width));
+        let result = loop {
+            let options = self.options.remaining(started, &stats);
+
+            let mut width_stats = SearchStats::default();
+            let mut dropped_nodes = false;
+            let result = self.search_width(width, &options, &mut width_stats, &mut dropped_nodes);
+            stats.add(&width_stats);
+            self.widths.push((width, width_stats.expanded_nodes));
+            match result {
+                Ok(node) => break Ok(node),
+                // A beam which never dropped a node searched everything, so a wider one can't help.
+                Err(SearcherError::GoalNotFoundError) if width < max_width && dropped_nodes => {
+                    width = width.saturating_mul(2).min(max_width);
+                }
+                Err(error) => break Err(error),
+            }
+        };
+        stats.elapsed = started.elapsed();
+        match result {
+            Ok(node) => {
+                stats.solution_depth = Some(node.get_depth());
+                Ok((node, stats))
+            }
+            Err(error) => Err((error, stats)),
+        }
+    }
+
+    fn search_width(&self,
+                    width: usize,
+                    options: &SearchOptions,
+                    stats: &mut SearchStats,
+                    dropped_nodes: &mut bool)
+                    -> Result<AStarNode, SearcherError> {
+        let limits = SearchLimits::new(options);
+        let mut directions = Directions::new(options);
+        let deadlocks = DeadlockDetector::new(&self.goal_world);
+        let state_size = state_size(&self.start_world);
+        let node_size = mem::size_of::<AStarNode>() + state_size;
+        let mut seen = HashSet::new();
+        seen.insert(self.start_world.state().clone());
+        let root_heuristic = self.heuristic.estimate(&self.start_world, &self.goal_world);
+        let mut beam = vec![AStarNode::new(0, self.start_world.clone(), None, 0, root_heuristic)];
+        stats.generated(0);
+        options.notify(SearchEvent::Generated {
+            world: &self.start_world,
+            depth: 0,
+        });
+
+        let mut depth = 0;
+        while !beam.is_empty() {
+            let goal_index = beam.iter()
+                .position(|node| node.get_world().eq_ignore_agent(&self.goal_world));
+            if let Some(index) = goal_index {
+                let goal_node = beam.swap_remove(index);
+                options.notify(SearchEvent::GoalFound {
+                    world: goal_node.get_world(),
+                    depth: goal_node.get_depth(),
+                });
+                return Ok(goal_node);
+            }
+            if options.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+                break;
+            }
+            depth += 1;
+
+            let mut children = Vec::new();
+            let mut layer = HashSet::new();
+            for parent in mem::take(&mut beam) {
+                limits.check(stats.expanded_nodes)?;
+                let parent_rc = Rc::new(parent);
+                options.notify(SearchEvent::Expanded {
+                    world: parent_rc.get_world(),
+                    depth: depth - 1,
+                });
+                for direction in directions.next().iter() {
+                    if let Ok(world) = parent_rc.get_world().clone_and_move_agent(direction) {
+                        stats.generated(depth);
+                        options.notify(SearchEvent::Generated {
+                            world: &world,
+                            depth,
+                        });
+                        if seen.contains(world.state()) || !layer.insert(world.state().clone()) {
+                            stats.duplicates_pruned += 1;
+                            options.notify(SearchEvent::Pruned {
+                                world: &world,
+                                depth,
+                                reason: PruneReason::Duplicate,
+                            });
+                            continue;
+                        }
+                        if deadlocks.is_deadlocked(&world) {
+                            stats.deadlocks_pruned += 1;
+                            options.notify(SearchEvent::Pruned {
+                                world: &world,
+                                depth,
+                                reason: PruneReason::Deadlock,
+                            });
+                            continue;
+                        }
+                        let heuristic = self.heuristic.estimate(&world, &self.goal_world);
+                        children.push(AStarNode::new(depth,
+                                                     world,
+                                                     Some(parent_rc.clone()),
+                                                     depth as usize,
+                                                     heuristic));
+                    }
+                }
+                stats.expanded(depth - 1);
+            }
+            stats.fringe(children.len(),
+                         children.len() * node_size + (seen.len() + layer.len()) * state_size);
+            // Stable, so ties keep the (possibly shuffled) order children were generated in.
+            children.sort_by_key(|node| node.get_heuristic());
+            *dropped_nodes |= children.len() > width;
+            for node in children.iter().skip(width) {
+                options.notify(SearchEvent::Pruned {
+                    world: node.get_world(),
+                    depth,
+                    reason: PruneReason::BeamWidth,
+                });
+            }
+            children.truncate(width);
+            // Worlds dropped from the beam can still be reached later, along another path.
+            seen.extend(children.iter().map(|node| node.get_world().state().clone()));
+            beam = children;
+        }
+        Err(SearcherError::GoalNotFoundError)
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::mem;
+use std::rc::Rc;
+use std::time::Instant;
+
+use super::BasicNode;
+use super::DeadlockDetector;
+use super::Node;
+use super::{PruneReason, SearchEvent};
+use super::Directions;
+use super::SearchLimits;
+use super::SearchOptions;
+use super::SearchStats;
+use super::SearcherError;
+use ::blocksworld::world;
+
+// Breadth first search from both ends at once, meeting in the middle.
+// The agent can finish anywhere, so the backward search starts from every goal world (one per free cell)
+// and steps back through every world each move could have come from (see World::previous_worlds).
+// Under push rules the forward search prunes deadlocked worlds; the backward one never reaches any.
+// Each search only has to reach about half the solution depth, so far fewer nodes are expanded than BFS.
+// Always a graph search: each half has to remember the worlds it reached to find where they meet.
+pub struct BidirectionalSearcher {
+    start_world: world::World,
+    goal_world: world::World,
+    options: SearchOptions,
+}
+impl BidirectionalSearcher {
+    pub fn new(start_world: world::World, goal_world: world::World) -> BidirectionalSearcher {
+        BidirectionalSearcher {
+            start_world,
+            goal_world,
+            options: SearchOptions::default(),
+        }
+    }
+    pub fn with_options(mut self, options: SearchOptions) -> BidirectionalSearcher {
+        self.options = options;
+        self
+    }
+    // Returns the same as the other searchers: the goal node, whose parents lead back to the start world.
+    // The plan found is optimal. The stats cover both halves, with the depths of the backward half
+    // counted back from the goal.
+    pub fn search(&mut self) -> Result<(BasicNode, SearchStats), (SearcherError, SearchStats)> {
+        let started = Instant::now();
+        let mut stats = SearchStats::default();
+        let result = self.search_halves(&mut stats);
+        stats.elapsed = started.elapsed();
+        match result {
+            Ok(goal_node) => {
+                stats.solution_depth = Some(goal_node.get_depth());
+                stats.suboptimality_bound = Some(1.0);
+                Ok((goal_node, stats))
+            }
+            Err(error) => Err((error, stats)),
+        }
+    }
+
+    fn search_halves(&self, stats: &mut SearchStats) -> Result<BasicNode, SearcherError> {
+        let limits = SearchLimits::new(&self.options);
+        let mut directions = Directions::new(&self.options);
+        let deadlocks = DeadlockDetector::new(&self.goal_world);
+        let mut forward = Half::new(vec![self.start_world.clone()]);
+        let mut backward = Half::new(self.goal_world.agent_placements());
+        for world in forward.frontier.iter().chain(backward.frontier.iter()) {
+            stats.generated(0);
+            self.options.notify(SearchEvent::Generated { world, depth: 0 });
+        }
+        if backward.reached.contains_key(self.start_world.state()) {
+            return Ok(self.found_goal(self.stitch(&forward, &backward, self.start_world.state())));
+        }
+        // Each world in a frontier, and each state reached along with the one it was reached from.
+        let heap_size = self.start_world.state().heap_size();
+        let world_size = mem::size_of::<world::World>() + heap_size;
+        let reached_size = mem::size_of::<(world::WorldState, Option<world::WorldState>)>() +
+                           2 * heap_size;
+
+        loop {
+            let fringe_size = forward.frontier.len() + backward.frontier.len();
+            let reached_len = forward.reached.len() + backward.reached.len();
+            stats.fringe(fringe_size, fringe_size * world_size + reached_len * reached_size);
+            // Both halves have finished layers this deep, and they haven't met,
+            // so the next layer is the first which could hold a plan this long.
+            let depth = forward.depth + backward.depth;
+            if self.options.max_depth.is_some_and(|max_depth| depth >= max_depth) ||
+               forward.frontier.is_empty() || backward.frontier.is_empty() {
+                return Err(SearcherError::GoalNotFoundError);
+            }
+            // Grow whichever half has the smaller frontier by a whole layer.
+            let is_forward = forward.frontier.len() <= backward.frontier.len();
+            let (half, other_half) = if is_forward {
+                (&mut forward, &backward)
+            } else {
+                (&mut backward, &forward)
+            };
+
+            let frontier = mem::take(&mut half.frontier);
+            for world in frontier {
+                limits.check(stats.expanded_nodes)?;
+                self.options.notify(SearchEvent::Expanded {
+                    world: &world,
+                    depth: half.depth,
+                });
+                for direction in directions.next().iter() {
+                    let neighbours: Vec<world::World> = if is_forward {
+                        world.clone_and_move_agent(direction).into_iter().collect()
+                    } else {
+                        world.previous_worlds(direction)
+                    };
+                    for neighbour in neighbours {
+                        stats.generated(half.depth + 1);
+                        self.options.notify(SearchEvent::Generated {
+                            world: &neighbour,
+                            depth: half.depth + 1,
+                        });
+                        if half.reached.contains_key(neighbour.state()) {
+                            stats.duplicates_pruned += 1;
+                            self.options.notify(SearchEvent::Pruned {
+                                world: &neighbour,
+                                depth: half.depth + 1,
+                                reason: PruneReason::Duplicate,
+                            });
+                            continue;
+                        }
+                        if is_forward && deadlocks.is_deadlocked(&neighbour) {
+                            stats.deadlocks_pruned += 1;
+                            self.options.notify(SearchEvent::Pruned {
+                                world: &neighbour,
+                                depth: half.depth + 1,
+                                reason: PruneReason::Deadlock,
+                            });
+                            continue;
+                        }
+                        half.reached.insert(neighbour.state().clone(), Some(world.state().clone()));
+                        // Neither half reached any world on a shorter plan, so the first meeting
+                        // is optimal.
+                        if other_half.reached.contains_key(neighbour.state()) {
+                            stats.expanded(half.depth);
+                            let meeting_state = neighbour.state().clone();
+                            let (forward, backward) = if is_forward {
+                                (&*half, other_half)
+                            } else {
+                                (other_half, &*half)
+                            };
+                            let goal_node = self.stitch(forward, backward, &meeting_state);
+                            return Ok(self.found_goal(goal_node));
+                        }
+                        half.frontier.push(neighbour);
+                    }
+                }
+                stats.expanded(half.depth);
+            }
+            half.depth += 1;
+        }
+    }
+
+    fn found_goal(&self, goal_node: BasicNode) -> BasicNode {
+        self.options.notify(SearchEvent::GoalFound {
+            world: goal_node.get_world(),
+            depth: goal_node.get_depth(),
+        });
+        goal_node
+    }
+
+    // Joins the path from the start world to the meeting world with the path from there to a goal world.
+    fn stitch(&self, forward: &Half, backward: &Half, meeting_state: &world::WorldState) -> BasicNode {
+        let mut states = forward.path_to_root(meeting_state);
+        states.reverse();
+        states.extend(backward.path_to_root(meeting_state).into_iter().skip(1));
+
+        let mut node = None;
+        for (depth, state) in states.into_iter().enumerate() {
+            let world = self.start_world.with_state(state);
+            node = Some(BasicNode::new(depth as u32, world, node.map(Rc::new)));
+        }
+        node.unwrap()
+    }
+}
+
+// One direction of the search.
+struct Half {
+    // Maps each world state reached to the one it was reached from, which is a step closer to the roots.
+    reached: HashMap<world::WorldState, Option<world::WorldState>>,
+    frontier: Vec<world::World>,
+    depth: u32,
+}
+impl Half {
+    fn new(roots: Vec<world::World>) -> Half {
+        Half {
+            reached: roots.iter().map(|world| (world.state().clone(), None)).collect(),
+            frontier: roots,
+            depth: 0,
+        }
+    }
+    // The states from this one back to the root it was reached from, inclusive.
+    fn path_to_root(&self, state: &world::WorldState) -> Vec<world::WorldState> {
+        let mut path = vec![state.clone()];
+        while let Some(Some(previous_state)) = self.reached.get(path.last().unwrap()) {
+            path.push(previous_state.clone());
+        }
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BidirectionalSearcher;
+    use blocksworld::search::Node;
+    use blocksworld::test::fixtures::test_puzzles;
+
+    #[test]
+    fn finds_optimal_plans() {
+        for test_puzzle in test_puzzles() {
+            let puzzle = test_puzzle.puzzle;
+            let (goal_node, _) = BidirectionalSearcher::new(puzzle.start, puzzle.goal.clone())
+                .search()
+                .unwrap();
+            assert_eq!(goal_node.get_depth(),
+                       test_puzzle.optimal_length,
+                       "{} under {} rules",
+                       test_puzzle.name,
+                       puzzle.goal.move_rules().name());
+            assert!(goal_node.get_world().eq_ignore_agent(&puzzle.goal));
+        }
+    }
+}
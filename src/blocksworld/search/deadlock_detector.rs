@@ -0,0 +1,192 @@
+use std::collections::VecDeque;
+
+use ::blocksworld::world;
+
+// Spots worlds which can no longer reach the goal under push rules, so searches can prune them.
+// A swap can always be undone, and a pull can drag a block back out of a corner, so this only finds
+// deadlocks when the goal world's rules are MoveRules::Push. Two kinds are found:
+// - a block on a dead square, one it can't be pushed to its goal from even with no other blocks
+//   in the way, e.g. a corner which isn't its goal;
+// - a frozen square: a 2x2 square filled by walls, blocks and the edge of the grid, holding a block
+//   which isn't on its goal. None of the blocks in it can ever be pushed again.
+// Neither ever prunes a world which can still reach the goal, so optimal searches stay optimal.
+#[derive(Clone, Debug)]
+pub struct DeadlockDetector {
+    // The goal cell of each block in layout order, empty unless the rules are push.
+    goal_cells: Vec<usize>,
+    // For each block, whether it can be pushed to its goal from each cell, indexed by cell.
+    live_cells: Vec<Vec<bool>>,
+}
+impl DeadlockDetector {
+    pub fn new(goal_world: &world::World) -> DeadlockDetector {
+        let mut detector = DeadlockDetector {
+            goal_cells: Vec::new(),
+            live_cells: Vec::new(),
+        };
+        if goal_world.move_rules() != world::MoveRules::Push {
+            return detector;
+        }
+        for (_, goal_location) in goal_world.blocks() {
+            detector.goal_cells.push(cell(goal_world, &goal_location));
+            detector.live_cells.push(live_cells(goal_world, &goal_location));
+        }
+        detector
+    }
+    pub fn is_deadlocked(&self, world: &world::World) -> bool {
+        world.blocks().enumerate().any(|(index, (_, location))| {
+            let cell = cell(world, &location);
+            index < self.live_cells.len() &&
+            (!self.live_cells[index][cell] ||
+             cell != self.goal_cells[index] && in_frozen_square(world, &location))
+        })
+    }
+}
+
+fn cell(world: &world::World, location: &world::Location) -> usize {
+    location.y() as usize * world.width() + location.x() as usize
+}
+
+// Breadth first back from the goal: a block reached a cell by being pushed from the cell behind it,
+// by the agent behind that, and neither of those can be a wall.
+fn live_cells(world: &world::World, goal_location: &world::Location) -> Vec<bool> {
+    let mut live_cells = vec![false; world.cell_count()];
+    live_cells[cell(world, goal_location)] = true;
+    let mut fringe = VecDeque::new();
+    fringe.push_back(goal_location.clone());
+    while let Some(location) = fringe.pop_front() {
+        for &direction in world::Direction::directions_array().iter() {
+            let from = location.step(direction.opposite());
+            let pusher = from.step(direction.opposite());
+            if is_open(world, &from) && is_open(world, &pusher) &&
+               !live_cells[cell(world, &from)] {
+                live_cells[cell(world, &from)] = true;
+                fringe.push_back(from);
+            }
+        }
+    }
+    live_cells
+}
+
+// Whether the location is in bounds and not a wall.
+fn is_open(world: &world::World, location: &world::Location) -> bool {
+    match world.get_grid_location(location) {
+        Ok(world::Entity::Wall) | Err(_) => false,
+        Ok(_) => true,
+    }
+}
+
+// Whether any of the four 2x2 squares holding the location is filled by walls, blocks and the edge.
+fn in_frozen_square(world: &world::World, location: &world::Location) -> bool {
+    let is_blocked = |x: isize, y: isize| {
+        match world.get_grid_location(&world::Location::new(x, y)) {
+            Ok(world::Entity::Wall) | Ok(world::Entity::Block(_)) | Err(_) => true,
+            Ok(_) => false,
+        }
+    };
+    let (x, y) = (location.x(), location.y());
+    [(x - 1, y - 1), (x - 1, y), (x, y - 1), (x, y)].iter().any(|&(left, top)| {
+        is_blocked(left, top) && is_blocked(left + 1, top) && is_blocked(left, top + 1) &&
+        is_blocked(left + 1, top + 1)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DeadlockDetector;
+    use blocksworld::puzzle::Puzzle;
+    use blocksworld::world::{MoveRules, World};
+
+    // A's goal is against the top edge, so it can only be pushed along it, and B's is beside it.
+    const GOAL: &str = "\
+* * * * * * *
+* @ A B     *
+*           *
+*           *
+* * * * * * *";
+
+    // The goal world and a world with the given grid, under the given rules.
+    fn worlds(grid: &str, move_rules: MoveRules) -> (World, World) {
+        let puzzle = Puzzle::parse(&format!("{}\n\n{}\n", grid, GOAL))
+            .unwrap()
+            .with_move_rules(move_rules);
+        (puzzle.goal, puzzle.start)
+    }
+
+    fn is_deadlocked(grid: &str) -> bool {
+        let (goal_world, world) = worlds(grid, MoveRules::Push);
+        DeadlockDetector::new(&goal_world).is_deadlocked(&world)
+    }
+
+    #[test]
+    fn finds_blocks_on_dead_squares() {
+        // A block in a corner which isn't its goal can never leave it.
+        assert!(is_deadlocked("\
+* * * * * * *
+* @     B   *
+*           *
+*         A *
+* * * * * * *"));
+        // Nor can one against an edge its goal isn't on.
+        assert!(is_deadlocked("\
+* * * * * * *
+* @     B   *
+*           *
+*     A     *
+* * * * * * *"));
+        // Along the goal's edge, or away from the edges, it can still get there.
+        assert!(!is_deadlocked("\
+* * * * * * *
+*       A   *
+*     @ B   *
+*           *
+* * * * * * *"));
+    }
+
+    #[test]
+    fn leaves_corners_which_are_goals() {
+        let goal = "\
+* * * * * * *
+* A B       *
+*           *
+*         @ *
+* * * * * * *";
+        let puzzle = Puzzle::parse(&format!("{}\n\n{}\n", goal, goal))
+            .unwrap()
+            .with_move_rules(MoveRules::Push);
+        assert!(!DeadlockDetector::new(&puzzle.goal).is_deadlocked(&puzzle.start));
+    }
+
+    #[test]
+    fn finds_frozen_squares_off_the_goal() {
+        // Both blocks can be pushed along the top edge alone, but not once they're side by side.
+        assert!(is_deadlocked("\
+* * * * * * *
+* @ B A     *
+*           *
+*           *
+* * * * * * *"));
+        // The same square with every block on its goal is the goal itself.
+        assert!(!is_deadlocked(GOAL));
+        // Below the edge the square has an open side.
+        assert!(!is_deadlocked("\
+* * * * * * *
+*     A     *
+* @   B     *
+*           *
+* * * * * * *"));
+    }
+
+    #[test]
+    fn only_finds_deadlocks_under_push_rules() {
+        let corner = "\
+* * * * * * *
+* @     B   *
+*           *
+*         A *
+* * * * * * *";
+        for &move_rules in &[MoveRules::Swap, MoveRules::PushPull] {
+            let (goal_world, world) = worlds(corner, move_rules);
+            assert!(!DeadlockDetector::new(&goal_world).is_deadlocked(&world));
+        }
+    }
+}
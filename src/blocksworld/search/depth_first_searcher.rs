@@ -1,8 +1,10 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::rc::Rc;
 
 use super::BasicNode;
 use super::Searcher;
+use super::SearchOptions;
+use super::SearchStats;
 use super::SearcherError;
 use ::blocksworld::world;
 
@@ -10,17 +12,30 @@ pub struct DepthFirstSearcher {
     start_world: world::World,
     goal_world: world::World,
     fringe: VecDeque<BasicNode>,
+    options: SearchOptions,
+    explored: HashMap<world::WorldState, u32>,
 }
 impl DepthFirstSearcher {
     pub fn new(start_world: world::World, goal_world: world::World) -> DepthFirstSearcher {
         DepthFirstSearcher {
-            start_world: start_world,
-            goal_world: goal_world,
+            start_world,
+            goal_world,
             fringe: VecDeque::new(),
+            options: SearchOptions::default(),
+            explored: HashMap::new(),
         }
     }
-    pub fn search(&mut self) -> Result<(BasicNode, u32), (SearcherError, u32)> {
-        Searcher::search(self, None)
+    pub fn with_options(mut self, options: SearchOptions) -> DepthFirstSearcher {
+        self.options = options;
+        self
+    }
+    // Enables graph search, so world states which have already been expanded are not expanded again.
+    pub fn with_graph_search(mut self) -> DepthFirstSearcher {
+        self.options.graph_search = true;
+        self
+    }
+    pub fn search(&mut self) -> Result<(BasicNode, SearchStats), (SearcherError, SearchStats)> {
+        Searcher::search(self)
     }
 }
 impl Searcher for DepthFirstSearcher {
@@ -31,15 +46,31 @@ impl Searcher for DepthFirstSearcher {
     fn get_goal_world(&self) -> &world::World {
         &self.goal_world
     }
+    fn get_options(&self) -> &SearchOptions {
+        &self.options
+    }
+    fn explored_states(&mut self) -> Option<&mut HashMap<world::WorldState, u32>> {
+        if self.options.graph_search {
+            Some(&mut self.explored)
+        } else {
+            None
+        }
+    }
     fn fringe_push(&mut self, node: Self::NodeType) {
         self.fringe.push_back(node);
     }
     fn fringe_pop(&mut self) -> Option<Self::NodeType> {
         self.fringe.pop_back()
     }
+    fn fringe_len(&self) -> usize {
+        self.fringe.len()
+    }
+    fn fringe_clear(&mut self) {
+        self.fringe.clear();
+    }
     fn new_node(&self,
                 depth: u32,
-                world: Box<world::World>,
+                world: world::World,
                 parent: Option<Rc<Self::NodeType>>)
                 -> Self::NodeType {
         Self::NodeType::new(depth, world, parent)
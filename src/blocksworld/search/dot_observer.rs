@@ -0,0 +1,356 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::io::{self, Write};
+
+use super::Heuristic;
+use super::Node;
+use super::{PruneReason, SearchEvent, SearchObserver};
+use ::blocksworld::world;
+
+// Records the nodes a search generates, to write them out as a Graphviz DOT graph, e.g.
+//   dot -Tsvg tree.dot -o tree.svg
+// Each node is labelled with its grid and depth (which is its g), and h and f if given a heuristic.
+// Expanded nodes are drawn solid, nodes still in the fringe dashed and pruned nodes dotted,
+// and the path to the goal is highlighted.
+//
+// By default every generated node is drawn, as a tree. The tree is rebuilt from the search's
+// events, so nodes of the same world at the same depth are taken to be expanded in the order they
+// were generated. Their subtrees are the same, but may be drawn under each other's parents.
+// With with_state_graph, nodes of the same world are merged instead, drawing the graph of states
+// the search reached, which suits graph searches.
+//
+// Only the first max_nodes nodes are drawn, so the graph stays small enough to render.
+// The goal found is only highlighted if it was drawn, so pass it to add_goal_path to draw the
+// rest of its path as well.
+// Iterative deepening starts the graph again for each deeper search; the searches of IDA* and
+// anytime are drawn side by side.
+pub struct DotTreeObserver {
+    heuristic: Option<(Box<dyn Heuristic>, world::World)>,
+    state_graph: bool,
+    max_nodes: usize,
+    nodes: Vec<DotNode>,
+    edges: Vec<(usize, usize)>,
+    edge_set: HashSet<(usize, usize)>,
+    // Tree: the nodes of each world and depth which haven't been expanded yet, oldest first.
+    unexpanded: HashMap<(world::WorldState, u32), VecDeque<usize>>,
+    // State graph: the node of each world.
+    state_nodes: HashMap<world::WorldState, usize>,
+    expanding: Option<usize>,
+    // Whether the node being expanded was left out, so its children should be too.
+    expanding_omitted: bool,
+    last_generated: Option<usize>,
+    goal: Option<usize>,
+    omitted_nodes: u64,
+}
+struct DotNode {
+    world: world::World,
+    depth: u32,
+    parent: Option<usize>,
+    expanded: bool,
+    pruned: Option<PruneReason>,
+}
+impl DotTreeObserver {
+    pub const DEFAULT_MAX_NODES: usize = 200;
+
+    pub fn new() -> DotTreeObserver {
+        DotTreeObserver {
+            heuristic: None,
+            state_graph: false,
+            max_nodes: DotTreeObserver::DEFAULT_MAX_NODES,
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            edge_set: HashSet::new(),
+            unexpanded: HashMap::new(),
+            state_nodes: HashMap::new(),
+            expanding: None,
+            expanding_omitted: false,
+            last_generated: None,
+            goal: None,
+            omitted_nodes: 0,
+        }
+    }
+    // Labels nodes with h and f as well, estimated towards the goal world.
+    pub fn with_heuristic<H: Heuristic + 'static>(mut self,
+                                                  heuristic: H,
+                                                  goal_world: world::World)
+                                                  -> DotTreeObserver {
+        self.heuristic = Some((Box::new(heuristic), goal_world));
+        self
+    }
+    // Merges nodes of the same world, drawing the state graph rather than the search tree.
+    pub fn with_state_graph(mut self) -> DotTreeObserver {
+        self.state_graph = true;
+        self
+    }
+    pub fn with_max_nodes(mut self, max_nodes: usize) -> DotTreeObserver {
+        self.max_nodes = max_nodes;
+        self
+    }
+    // Highlights the path to the goal node a search returned, drawing the nodes on it which were
+    // left out, however many nodes have been drawn already.
+    pub fn add_goal_path<N: Node>(&mut self, goal_node: &N) {
+        let mut path = vec![goal_node.get_world().clone()];
+        let mut node = goal_node.get_parent();
+        while let Some(parent) = node {
+            path.push(parent.get_world().clone());
+            node = parent.get_parent();
+        }
+        path.reverse();
+
+        let mut previous: Option<usize> = None;
+        for (depth, world) in path.into_iter().enumerate() {
+            let depth = depth as u32;
+            let drawn = if self.state_graph {
+                self.state_nodes.get(world.state()).cloned()
+            } else {
+                self.nodes.iter().rposition(|node| {
+                    node.parent == previous && node.depth == depth &&
+                    node.world.state() == world.state()
+                })
+            };
+            let id = match drawn {
+                Some(id) => id,
+                None => {
+                    if self.state_graph {
+                        self.state_nodes.insert(world.state().clone(), self.nodes.len());
+                    }
+                    self.nodes.push(DotNode {
+                        world,
+                        depth,
+                        parent: previous,
+                        expanded: true,
+                        pruned: None,
+                    });
+                    self.nodes.len() - 1
+                }
+            };
+            // In the state graph, the world may have been drawn as reached another way first.
+            self.nodes[id].parent = previous;
+            if let Some(previous) = previous {
+                self.add_edge(previous, id);
+            }
+            previous = Some(id);
+        }
+        if let Some(goal) = previous {
+            self.nodes[goal].expanded = false;
+        }
+        self.goal = previous;
+    }
+    // Writes the graph recorded so far, as a digraph with the given name.
+    pub fn write_dot<W: Write>(&self, writer: &mut W, name: &str) -> io::Result<()> {
+        let mut on_goal_path = HashSet::new();
+        let mut node = self.goal;
+        while let Some(id) = node {
+            if !on_goal_path.insert(id) {
+                break;
+            }
+            node = self.nodes[id].parent;
+        }
+
+        writeln!(writer, "digraph {} {{", dot_string(name))?;
+        writeln!(writer, "    node [shape=box, fontname=\"monospace\"];")?;
+        for (id, node) in self.nodes.iter().enumerate() {
+            let mut styles = vec![if node.pruned.is_some() {
+                                      "dotted"
+                                  } else if node.expanded {
+                                      "solid"
+                                  } else {
+                                      "dashed"
+                                  }];
+            if Some(id) == self.goal {
+                styles.push("filled");
+            }
+            let mut attributes = format!("label={}, style=\"{}\"",
+                                         dot_label(&self.label(node)),
+                                         styles.join(","));
+            if on_goal_path.contains(&id) {
+                attributes += ", color=red, penwidth=2, fillcolor=mistyrose";
+            }
+            writeln!(writer, "    n{} [{}];", id, attributes)?;
+        }
+        for &(parent, child) in &self.edges {
+            let on_path = on_goal_path.contains(&child) && self.nodes[child].parent == Some(parent);
+            let attributes = if on_path { " [color=red, penwidth=2]" } else { "" };
+            writeln!(writer, "    n{} -> n{}{};", parent, child, attributes)?;
+        }
+        if self.omitted_nodes > 0 {
+            writeln!(writer,
+                     "    omitted [shape=plaintext, label=\"{} more nodes not drawn\"];",
+                     self.omitted_nodes)?;
+        }
+        writeln!(writer, "}}")
+    }
+
+    // The lines of a node's label.
+    fn label(&self, node: &DotNode) -> Vec<String> {
+        let mut label = Vec::new();
+        for y in 0..node.world.height() {
+            label.push((0..node.world.width())
+                .map(|x| {
+                    let location = world::Location::new(x as isize, y as isize);
+                    match node.world.get_grid_location(&location).unwrap() {
+                        world::Entity::Agent => '@',
+                        world::Entity::Block(block_char) => block_char,
+                        world::Entity::Wall => '#',
+                        world::Entity::None => '.',
+                    }
+                })
+                .collect());
+        }
+        label.push(format!("depth {}", node.depth));
+        if let Some((ref heuristic, ref goal_world)) = self.heuristic {
+            let estimate = heuristic.estimate(&node.world, goal_world);
+            label.push(format!("g {}  h {}  f {}",
+                               node.depth,
+                               estimate,
+                               node.depth as usize + estimate));
+        }
+        if let Some(reason) = node.pruned {
+            label.push(format!("pruned: {}", reason.name()));
+        }
+        label
+    }
+
+    fn clear(&mut self) {
+        self.nodes.clear();
+        self.edges.clear();
+        self.edge_set.clear();
+        self.unexpanded.clear();
+        self.state_nodes.clear();
+        self.expanding = None;
+        self.expanding_omitted = false;
+        self.last_generated = None;
+        self.goal = None;
+        self.omitted_nodes = 0;
+    }
+
+    fn generated(&mut self, world: &world::World, depth: u32) {
+        self.last_generated = None;
+        let parent = if depth == 0 { None } else { self.expanding };
+        if depth > 0 && self.expanding_omitted {
+            self.omitted_nodes += 1;
+            return;
+        }
+        if self.state_graph {
+            if let Some(&id) = self.state_nodes.get(world.state()) {
+                if let Some(parent) = parent {
+                    self.add_edge(parent, id);
+                }
+                let node = &mut self.nodes[id];
+                if depth < node.depth {
+                    node.depth = depth;
+                    node.parent = parent;
+                }
+                self.last_generated = Some(id);
+                return;
+            }
+        }
+        if self.nodes.len() >= self.max_nodes {
+            self.omitted_nodes += 1;
+            return;
+        }
+
+        let id = self.nodes.len();
+        self.nodes.push(DotNode {
+            world: world.clone(),
+            depth,
+            parent,
+            expanded: false,
+            pruned: None,
+        });
+        if let Some(parent) = parent {
+            self.add_edge(parent, id);
+        }
+        if self.state_graph {
+            self.state_nodes.insert(world.state().clone(), id);
+        } else {
+            self.unexpanded.entry((world.state().clone(), depth)).or_default().push_back(id);
+        }
+        self.last_generated = Some(id);
+    }
+
+    fn add_edge(&mut self, parent: usize, child: usize) {
+        if self.edge_set.insert((parent, child)) {
+            self.edges.push((parent, child));
+        }
+    }
+
+    // The node an event about an existing node refers to, which stops being unexpanded.
+    fn take_node(&mut self, world: &world::World, depth: u32) -> Option<usize> {
+        if self.state_graph {
+            return self.state_nodes.get(world.state()).cloned();
+        }
+        let key = (world.state().clone(), depth);
+        let candidates = self.unexpanded.get_mut(&key)?;
+        // A node pruned as soon as it was generated is the newest one.
+        let is_last_generated = self.last_generated
+            .is_some_and(|id| candidates.back() == Some(&id));
+        let id = if is_last_generated {
+            candidates.pop_back()
+        } else {
+            candidates.pop_front()
+        };
+        if candidates.is_empty() {
+            self.unexpanded.remove(&key);
+        }
+        id
+    }
+}
+impl Default for DotTreeObserver {
+    fn default() -> DotTreeObserver {
+        DotTreeObserver::new()
+    }
+}
+impl fmt::Debug for DotTreeObserver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DotTreeObserver")
+            .field("state_graph", &self.state_graph)
+            .field("max_nodes", &self.max_nodes)
+            .field("nodes", &self.nodes.len())
+            .field("omitted_nodes", &self.omitted_nodes)
+            .finish()
+    }
+}
+impl SearchObserver for DotTreeObserver {
+    fn observe(&mut self, event: &SearchEvent) {
+        match *event {
+            SearchEvent::Generated { world, depth } => self.generated(world, depth),
+            SearchEvent::Expanded { world, depth } => {
+                self.expanding = self.take_node(world, depth);
+                self.expanding_omitted = self.expanding.is_none();
+                if let Some(id) = self.expanding {
+                    self.nodes[id].expanded = true;
+                }
+            }
+            // In the state graph, the edge to the world is all there is to show for a duplicate.
+            SearchEvent::Pruned { reason: PruneReason::Duplicate, .. } if self.state_graph => {}
+            SearchEvent::Pruned { world, depth, reason } => {
+                if let Some(id) = self.take_node(world, depth) {
+                    if !self.nodes[id].expanded {
+                        self.nodes[id].pruned = Some(reason);
+                    }
+                }
+            }
+            SearchEvent::GoalFound { world, depth } => {
+                self.goal = self.take_node(world, depth).or(self.goal);
+            }
+            SearchEvent::DepthIncreased { .. } => self.clear(),
+        }
+        if let SearchEvent::Generated { .. } = *event {
+            return;
+        }
+        self.last_generated = None;
+    }
+}
+
+fn dot_string(text: &str) -> String {
+    format!("\"{}\"", escape(text))
+}
+// Left aligns each line, ending it with \l.
+fn dot_label(lines: &[String]) -> String {
+    format!("\"{}\"", lines.iter().map(|line| escape(line) + "\\l").collect::<String>())
+}
+// Backslashes first, so the ones escaping quotes aren't escaped again.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
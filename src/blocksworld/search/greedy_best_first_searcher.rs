@@ -0,0 +1,134 @@
+use std::collections::{BinaryHeap, HashMap};
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use super::AStarNode;
+use super::{Heuristic, ManhattanHeuristic};
+use super::Searcher;
+use super::SearchOptions;
+use super::SearchStats;
+use super::SearcherError;
+use ::blocksworld::world;
+
+// Always expands the node which looks closest to the goal, ignoring how many moves it took to reach.
+// Usually expands far fewer nodes than A*, but the plans it finds can be much longer than optimal.
+// Like A* it always performs a graph search, as otherwise it can loop between two promising worlds.
+pub struct GreedyBestFirstSearcher {
+    start_world: world::World,
+    goal_world: world::World,
+    fringe: BinaryHeap<ByHeuristic>,
+    options: SearchOptions,
+    explored: HashMap<world::WorldState, u32>,
+    heuristic: Box<dyn Heuristic>,
+}
+impl GreedyBestFirstSearcher {
+    pub fn new(start_world: world::World, goal_world: world::World) -> GreedyBestFirstSearcher {
+        GreedyBestFirstSearcher {
+            start_world,
+            goal_world,
+            fringe: BinaryHeap::new(),
+            options: SearchOptions::default(),
+            explored: HashMap::new(),
+            heuristic: Box::new(ManhattanHeuristic),
+        }
+    }
+    pub fn with_options(mut self, options: SearchOptions) -> GreedyBestFirstSearcher {
+        self.options = options;
+        self
+    }
+    // The heuristic doesn't need to be admissible, as the plans aren't optimal anyway.
+    pub fn with_heuristic<H: Heuristic + 'static>(mut self, heuristic: H) -> GreedyBestFirstSearcher {
+        self.heuristic = Box::new(heuristic);
+        self
+    }
+    pub fn search(&mut self) -> Result<(AStarNode, SearchStats), (SearcherError, SearchStats)> {
+        Searcher::search(self)
+    }
+}
+impl Searcher for GreedyBestFirstSearcher {
+    type NodeType = AStarNode;
+    fn get_start_world(&self) -> &world::World {
+        &self.start_world
+    }
+    fn get_goal_world(&self) -> &world::World {
+        &self.goal_world
+    }
+    // Greedy search ignores the cost of the moves already made, so there is no bound on how much
+    // longer than optimal its plans can be.
+    fn suboptimality_bound(&self) -> Option<f64> {
+        None
+    }
+    fn get_options(&self) -> &SearchOptions {
+        &self.options
+    }
+    fn explored_states(&mut self) -> Option<&mut HashMap<world::WorldState, u32>> {
+        Some(&mut self.explored)
+    }
+    fn fringe_push(&mut self, node: Self::NodeType) {
+        self.fringe.push(ByHeuristic(node));
+    }
+    fn fringe_pop(&mut self) -> Option<Self::NodeType> {
+        self.fringe.pop().map(|ByHeuristic(node)| node)
+    }
+    fn fringe_len(&self) -> usize {
+        self.fringe.len()
+    }
+    fn fringe_clear(&mut self) {
+        self.fringe.clear();
+    }
+    fn new_node(&self,
+                depth: u32,
+                world: world::World,
+                parent: Option<Rc<Self::NodeType>>)
+                -> Self::NodeType {
+        let heuristic = self.heuristic.estimate(&world, self.get_goal_world());
+        let start_to_self_cost = match parent {
+            Some(ref parent_rc) => parent_rc.get_start_to_self_cost() + 1,
+            None => 0,
+        };
+        AStarNode::new(depth, world, parent, start_to_self_cost, heuristic)
+    }
+}
+
+// Orders nodes by their heuristic alone, minimum at the top of the priority queue.
+struct ByHeuristic(AStarNode);
+impl PartialEq for ByHeuristic {
+    fn eq(&self, other: &ByHeuristic) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for ByHeuristic {}
+impl PartialOrd for ByHeuristic {
+    fn partial_cmp(&self, other: &ByHeuristic) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for ByHeuristic {
+    fn cmp(&self, other: &ByHeuristic) -> Ordering {
+        other.0.get_heuristic().cmp(&self.0.get_heuristic())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GreedyBestFirstSearcher;
+    use blocksworld::puzzle::Puzzle;
+    use blocksworld::search::{DirectionOrder, Node, SearchOptions, Searcher};
+
+    #[test]
+    fn searches_again_from_scratch() {
+        let puzzle = Puzzle::parse(include_str!("../../../puzzles/readme.txt")).unwrap();
+        let mut searcher = GreedyBestFirstSearcher::new(puzzle.start, puzzle.goal.clone())
+            .with_options(SearchOptions {
+                direction_order: DirectionOrder::Fixed,
+                ..SearchOptions::default()
+            });
+        // Through the trait, which doesn't go through the inherent search.
+        let (first_goal_node, first_stats) = Searcher::search(&mut searcher).unwrap();
+        let (second_goal_node, second_stats) = Searcher::search(&mut searcher).unwrap();
+        assert!(second_goal_node.get_world().eq_ignore_agent(&puzzle.goal));
+        assert_eq!(second_goal_node.get_depth(), first_goal_node.get_depth());
+        assert_eq!(second_stats.expanded_nodes, first_stats.expanded_nodes);
+        assert_eq!(second_stats.generated_nodes, first_stats.generated_nodes);
+    }
+}
@@ -0,0 +1,113 @@
+use std::rc::Rc;
+
+use ::blocksworld::world;
+
+// An estimate of the number of moves left from a world to the goal world, for the informed searchers.
+// A* and IDA* only find optimal plans if the estimate is admissible (never more than the real number).
+// All the built in heuristics are; check_admissible can test new ones on small puzzles.
+pub trait Heuristic {
+    fn estimate(&self, world: &world::World, goal_world: &world::World) -> usize;
+}
+// Lets one heuristic be shared between searchers, or chosen at runtime as an Rc<dyn Heuristic>.
+impl<H: Heuristic + ?Sized> Heuristic for Rc<H> {
+    fn estimate(&self, world: &world::World, goal_world: &world::World) -> usize {
+        (**self).estimate(world, goal_world)
+    }
+}
+
+// Sum of each block's manhattan distance to its goal location. The default for every searcher.
+// A move swaps the agent with at most one block, moving it one cell, so this is admissible.
+// Pushes do the same, but a push and pull moves two blocks, so under those rules it's halved.
+// Blocks can't pass through walls either, so around walls the distance is walked around them.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ManhattanHeuristic;
+impl Heuristic for ManhattanHeuristic {
+    fn estimate(&self, world: &world::World, goal_world: &world::World) -> usize {
+        world.blocks()
+            .map(|(block_char, loc)| {
+                let goal_loc = goal_world.get_entity_location(&world::Entity::Block(block_char))
+                    .unwrap();
+                world.walking_distance(&loc, &goal_loc)
+            })
+            .sum::<usize>()
+            .div_ceil(world.move_rules().blocks_per_move())
+    }
+}
+
+// Manhattan, plus the moves the agent needs to reach the nearest misplaced block (both walked
+// around walls).
+// Until the agent is next to a misplaced block it can only move blocks which are already placed,
+// which can't lower the manhattan sum, so those moves come on top of it.
+// This is admissible but not consistent, which A* allows for by reopening states.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AgentHeuristic;
+impl Heuristic for AgentHeuristic {
+    fn estimate(&self, world: &world::World, goal_world: &world::World) -> usize {
+        let agent_loc = world.get_entity_location(&world::Entity::Agent).unwrap();
+        let mut manhattan = 0;
+        let mut nearest_misplaced = None;
+        for ((_, loc), (_, goal_loc)) in world.blocks().zip(goal_world.blocks()) {
+            let distance = world.walking_distance(&loc, &goal_loc);
+            if distance > 0 {
+                manhattan += distance;
+                let agent_distance = world.walking_distance(&agent_loc, &loc);
+                nearest_misplaced = Some(nearest_misplaced.map_or(agent_distance, |nearest: usize| {
+                    nearest.min(agent_distance)
+                }));
+            }
+        }
+        // The agent only has to get next to the block, not onto it.
+        manhattan.div_ceil(world.move_rules().blocks_per_move()) +
+        nearest_misplaced.map_or(0, |nearest| nearest - 1)
+    }
+}
+
+// The largest of several estimates, which is admissible if each of them is.
+#[derive(Default)]
+pub struct MaxHeuristic {
+    heuristics: Vec<Box<dyn Heuristic>>,
+}
+impl MaxHeuristic {
+    pub fn new() -> MaxHeuristic {
+        MaxHeuristic::default()
+    }
+    pub fn with<H: Heuristic + 'static>(mut self, heuristic: H) -> MaxHeuristic {
+        self.heuristics.push(Box::new(heuristic));
+        self
+    }
+}
+impl Heuristic for MaxHeuristic {
+    fn estimate(&self, world: &world::World, goal_world: &world::World) -> usize {
+        self.heuristics
+            .iter()
+            .map(|heuristic| heuristic.estimate(world, goal_world))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+// Several estimates, each multiplied by its weight and added up, rounded down.
+// This is admissible if each estimate is and the weights add up to at most 1.
+// Larger weights trade optimality for fewer expanded nodes.
+#[derive(Default)]
+pub struct WeightedSumHeuristic {
+    terms: Vec<(f64, Box<dyn Heuristic>)>,
+}
+impl WeightedSumHeuristic {
+    pub fn new() -> WeightedSumHeuristic {
+        WeightedSumHeuristic::default()
+    }
+    pub fn with<H: Heuristic + 'static>(mut self, weight: f64, heuristic: H) -> WeightedSumHeuristic {
+        self.terms.push((weight, Box::new(heuristic)));
+        self
+    }
+}
+impl Heuristic for WeightedSumHeuristic {
+    fn estimate(&self, world: &world::World, goal_world: &world::World) -> usize {
+        let sum = self.terms
+            .iter()
+            .map(|&(weight, ref heuristic)| weight * heuristic.estimate(world, goal_world) as f64)
+            .sum::<f64>();
+        sum.max(0.0).floor() as usize
+    }
+}
@@ -0,0 +1,192 @@
+use std::rc::Rc;
+use std::time::Instant;
+
+use super::AStarNode;
+use super::Node;
+use super::{PruneReason, SearchEvent};
+use super::Searcher;
+use super::SearchOptions;
+use super::SearchStats;
+use super::SearcherError;
+use super::{Heuristic, ManhattanHeuristic};
+use ::blocksworld::world;
+
+// Iterative deepening A*: repeated depth first searches, each pruning nodes whose f(n) exceeds a threshold.
+// The threshold starts at the start world's heuristic and is raised to the smallest f(n) that was pruned,
+// so like A* the first goal found is optimal, but only the current path and its siblings are kept in memory.
+pub struct IdaStarSearcher {
+    start_world: world::World,
+    goal_world: world::World,
+    fringe: Vec<AStarNode>,
+    options: SearchOptions,
+    threshold: usize,
+    next_threshold: Option<usize>,
+    iterations: Vec<(usize, u32)>,
+    heuristic: Box<dyn Heuristic>,
+}
+impl IdaStarSearcher {
+    pub fn new(start_world: world::World, goal_world: world::World) -> IdaStarSearcher {
+        IdaStarSearcher {
+            start_world,
+            goal_world,
+            fringe: Vec::new(),
+            options: SearchOptions::default(),
+            threshold: 0,
+            next_threshold: None,
+            iterations: Vec::new(),
+            heuristic: Box::new(ManhattanHeuristic),
+        }
+    }
+    pub fn with_options(mut self, options: SearchOptions) -> IdaStarSearcher {
+        self.options = options;
+        self
+    }
+    // Like A*, plans are only optimal with an admissible heuristic.
+    pub fn with_heuristic<H: Heuristic + 'static>(mut self, heuristic: H) -> IdaStarSearcher {
+        self.heuristic = Box::new(heuristic);
+        self
+    }
+    pub fn search(&mut self) -> Result<(AStarNode, SearchStats), (SearcherError, SearchStats)> {
+        let options = self.options.clone();
+        let result = self.search_iterations(&options);
+        self.options = options;
+        result
+    }
+    // The f(n) threshold and number of expanded nodes of each iteration of the last search.
+    pub fn iterations(&self) -> &[(usize, u32)] {
+        &self.iterations
+    }
+
+    fn search_iterations(&mut self,
+                         options: &SearchOptions)
+                         -> Result<(AStarNode, SearchStats), (SearcherError, SearchStats)> {
+        let started = Instant::now();
+        let mut stats = SearchStats::default();
+        self.iterations.clear();
+        self.threshold = self.heuristic.estimate(&self.start_world, &self.goal_world);
+        let result = loop {
+            self.next_threshold = None;
+            self.options = options.remaining(started, &stats);
+            let search = Searcher::search(self);
+            let iteration_stats = match search {
+                Ok((_, ref iteration_stats)) => iteration_stats,
+                Err((_, ref iteration_stats)) => iteration_stats,
+            };
+            stats.add(iteration_stats);
+            stats.solution_depth = iteration_stats.solution_depth;
+            stats.suboptimality_bound = iteration_stats.suboptimality_bound;
+            self.iterations.push((self.threshold, iteration_stats.expanded_nodes));
+            match search {
+                Ok((node, _)) => break Ok(node),
+                Err((SearcherError::GoalNotFoundError, _)) => {
+                    match self.next_threshold {
+                        Some(next_threshold) => self.threshold = next_threshold,
+                        // Nothing was pruned, so raising the threshold can't reach any more worlds.
+                        None => break Err(SearcherError::GoalNotFoundError),
+                    }
+                }
+                Err((error, _)) => break Err(error),
+            }
+        };
+        stats.elapsed = started.elapsed();
+        match result {
+            Ok(node) => Ok((node, stats)),
+            Err(error) => Err((error, stats)),
+        }
+    }
+}
+impl Searcher for IdaStarSearcher {
+    type NodeType = AStarNode;
+    fn get_start_world(&self) -> &world::World {
+        &self.start_world
+    }
+    fn get_goal_world(&self) -> &world::World {
+        &self.goal_world
+    }
+    // With an admissible heuristic, each threshold only rises to the cheapest f(n) pruned, so the
+    // first goal found is optimal.
+    fn suboptimality_bound(&self) -> Option<f64> {
+        Some(1.0)
+    }
+    fn get_options(&self) -> &SearchOptions {
+        &self.options
+    }
+    fn fringe_push(&mut self, node: Self::NodeType) {
+        let total_cost = node.get_total_cost();
+        if total_cost > self.threshold {
+            self.next_threshold = Some(self.next_threshold
+                .map_or(total_cost, |next_threshold| next_threshold.min(total_cost)));
+            self.options.notify(SearchEvent::Pruned {
+                world: node.get_world(),
+                depth: node.get_depth(),
+                reason: PruneReason::CostBound,
+            });
+            return;
+        }
+        self.fringe.push(node);
+    }
+    fn fringe_pop(&mut self) -> Option<Self::NodeType> {
+        self.fringe.pop()
+    }
+    fn fringe_len(&self) -> usize {
+        self.fringe.len()
+    }
+    fn fringe_clear(&mut self) {
+        self.fringe.clear();
+    }
+    fn new_node(&self,
+                depth: u32,
+                world: world::World,
+                parent: Option<Rc<Self::NodeType>>)
+                -> Self::NodeType {
+        let heuristic = self.heuristic.estimate(&world, self.get_goal_world());
+        let start_to_self_cost = match parent { // Each node is only 1 move away from its parent.
+            Some(ref parent_rc) => parent_rc.get_start_to_self_cost() + 1,
+            None => 0,
+        };
+        AStarNode::new(depth, world, parent, start_to_self_cost, heuristic)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IdaStarSearcher;
+    use blocksworld::puzzle::Puzzle;
+    use blocksworld::search::{AgentHeuristic, MaxHeuristic, Node, PatternDatabaseBuilder};
+    use blocksworld::test::fixtures::{test_puzzles, TestPuzzle};
+
+    #[test]
+    fn finds_optimal_plans() {
+        for TestPuzzle { name, puzzle, optimal_length } in test_puzzles() {
+            let move_rules = puzzle.goal.move_rules();
+            // Tree searches with the manhattan heuristic take minutes on the larger puzzles.
+            let heuristic = MaxHeuristic::new()
+                .with(AgentHeuristic)
+                .with(PatternDatabaseBuilder::new(&puzzle.goal).build_heuristic().unwrap());
+            let (goal_node, _) = IdaStarSearcher::new(puzzle.start, puzzle.goal)
+                .with_heuristic(heuristic)
+                .search()
+                .unwrap();
+            assert_eq!(goal_node.get_depth(),
+                       optimal_length,
+                       "{} under {} rules",
+                       name,
+                       move_rules.name());
+        }
+    }
+
+    #[test]
+    fn raises_the_threshold_each_iteration() {
+        let puzzle = Puzzle::parse(include_str!("../../../puzzles/readme.txt")).unwrap();
+        let mut searcher = IdaStarSearcher::new(puzzle.start, puzzle.goal);
+        let (goal_node, _) = searcher.search().unwrap();
+        let thresholds = searcher.iterations()
+            .iter()
+            .map(|&(threshold, _)| threshold)
+            .collect::<Vec<usize>>();
+        assert!(thresholds.len() > 1);
+        assert!(thresholds.windows(2).all(|pair| pair[0] <= pair[1]));
+        // The goal is found by the iteration whose threshold is the optimal plan length.
+        assert_eq!(thresholds.last(), Some(&(goal_node.get_depth() as usize)));
+    }
+}
@@ -1,8 +1,12 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::rc::Rc;
+use std::time::Instant;
 
 use super::BasicNode;
+use super::SearchEvent;
 use super::Searcher;
+use super::SearchOptions;
+use super::SearchStats;
 use super::SearcherError;
 use ::blocksworld::world;
 
@@ -10,30 +14,64 @@ pub struct IterativeDeepeningSearcher {
     start_world: world::World,
     goal_world: world::World,
     fringe: VecDeque<BasicNode>,
+    options: SearchOptions,
+    explored: HashMap<world::WorldState, u32>,
 }
 impl IterativeDeepeningSearcher {
     pub fn new(start_world: world::World, goal_world: world::World) -> IterativeDeepeningSearcher {
         IterativeDeepeningSearcher {
-            start_world: start_world,
-            goal_world: goal_world,
+            start_world,
+            goal_world,
             fringe: VecDeque::new(),
+            options: SearchOptions::default(),
+            explored: HashMap::new(),
         }
     }
-    pub fn search(&mut self) -> Result<(BasicNode, u32), (SearcherError, u32)> {
-        let mut expanded_nodes = 0;
-        // Increase the max depth from zero until the goal is found
-        // Sums up the expanded nodes for every iteration
-        (0..)
-            .map(|max_depth| {
-                let search = Searcher::search(self, Some(max_depth));
-                expanded_nodes += match search {
-                    Ok((_, exp_nod)) => exp_nod,
-                    Err((_, exp_nod)) => exp_nod,
-                };
-                search
-            })
-            .find(|result| result.is_ok())
-            .unwrap()
+    pub fn with_options(mut self, options: SearchOptions) -> IterativeDeepeningSearcher {
+        self.options = options;
+        self
+    }
+    // Enables graph search, so world states which have already been expanded are not expanded again.
+    pub fn with_graph_search(mut self) -> IterativeDeepeningSearcher {
+        self.options.graph_search = true;
+        self
+    }
+    pub fn search(&mut self) -> Result<(BasicNode, SearchStats), (SearcherError, SearchStats)> {
+        let options = self.options.clone();
+        let started = Instant::now();
+        let mut stats = SearchStats::default();
+        // Increase the max depth from zero until the goal is found, or the max depth option is reached
+        // Adds up the stats of every iteration
+        let mut max_depth = 0;
+        let result = loop {
+            self.options = options.remaining(started, &stats);
+            self.options.max_depth = Some(max_depth);
+            match Searcher::search(self) {
+                Ok((node, iteration_stats)) => {
+                    stats.add(&iteration_stats);
+                    stats.solution_depth = iteration_stats.solution_depth;
+                    stats.suboptimality_bound = iteration_stats.suboptimality_bound;
+                    break Ok(node);
+                }
+                Err((SearcherError::GoalNotFoundError, iteration_stats)) if options.max_depth
+                    .is_none_or(|option_max_depth| max_depth < option_max_depth) => {
+                    stats.add(&iteration_stats);
+                    max_depth += 1;
+                    options.notify(SearchEvent::DepthIncreased { max_depth });
+                }
+                Err((error, iteration_stats)) => {
+                    stats.add(&iteration_stats);
+                    break Err(error);
+                }
+            }
+        };
+        stats.elapsed = started.elapsed();
+        let result = match result {
+            Ok(node) => Ok((node, stats)),
+            Err(error) => Err((error, stats)),
+        };
+        self.options = options;
+        result
     }
 }
 impl Searcher for IterativeDeepeningSearcher {
@@ -44,15 +82,36 @@ impl Searcher for IterativeDeepeningSearcher {
     fn get_goal_world(&self) -> &world::World {
         &self.goal_world
     }
+    // Each iteration only searches one move deeper than the last, so the first goal found is the
+    // shallowest.
+    fn suboptimality_bound(&self) -> Option<f64> {
+        Some(1.0)
+    }
+    fn get_options(&self) -> &SearchOptions {
+        &self.options
+    }
+    fn explored_states(&mut self) -> Option<&mut HashMap<world::WorldState, u32>> {
+        if self.options.graph_search {
+            Some(&mut self.explored)
+        } else {
+            None
+        }
+    }
     fn fringe_push(&mut self, node: Self::NodeType) {
         self.fringe.push_back(node);
     }
     fn fringe_pop(&mut self) -> Option<Self::NodeType> {
         self.fringe.pop_back()
     }
+    fn fringe_len(&self) -> usize {
+        self.fringe.len()
+    }
+    fn fringe_clear(&mut self) {
+        self.fringe.clear();
+    }
     fn new_node(&self,
                 depth: u32,
-                world: Box<world::World>,
+                world: world::World,
                 parent: Option<Rc<Self::NodeType>>)
                 -> Self::NodeType {
         Self::NodeType::new(depth, world, parent)
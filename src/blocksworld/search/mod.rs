@@ -1,83 +1,318 @@
 extern crate rand;
 
 use ::blocksworld::world;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem;
 use std::rc::Rc;
-use self::rand::{thread_rng, Rng};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use self::rand::{Rng, SeedableRng, StdRng};
 
 mod breadth_first_searcher;
 mod depth_first_searcher;
 mod iterative_deepening_searcher;
 mod a_star_searcher;
+mod ida_star_searcher;
+mod greedy_best_first_searcher;
+mod anytime_searcher;
+mod beam_searcher;
+mod bidirectional_searcher;
+mod optimal_plans_searcher;
+mod heuristics;
+mod pattern_database;
+mod search_stats;
+mod observer;
+mod dot_observer;
+mod deadlock_detector;
 pub use self::breadth_first_searcher::BreadthFirstSearcher;
 pub use self::depth_first_searcher::DepthFirstSearcher;
 pub use self::iterative_deepening_searcher::IterativeDeepeningSearcher;
-pub use self::a_star_searcher::AStarSearcher;
+pub use self::a_star_searcher::{AStarSearcher, AStarNode};
+pub use self::ida_star_searcher::IdaStarSearcher;
+pub use self::greedy_best_first_searcher::GreedyBestFirstSearcher;
+pub use self::anytime_searcher::AnytimeSearcher;
+pub use self::beam_searcher::BeamSearcher;
+pub use self::bidirectional_searcher::BidirectionalSearcher;
+pub use self::optimal_plans_searcher::{OptimalPlansSearcher, OptimalPlans};
+pub use self::heuristics::{Heuristic, ManhattanHeuristic, AgentHeuristic, MaxHeuristic,
+                           WeightedSumHeuristic};
+pub use self::pattern_database::{PatternDatabase, PatternDatabaseBuilder, PatternDatabaseError};
+pub use self::search_stats::{SearchStats, DepthStats};
+pub use self::observer::{SearchObserver, SearchEvent, PruneReason, JsonTraceObserver};
+pub use self::dot_observer::DotTreeObserver;
+pub use self::deadlock_detector::DeadlockDetector;
+
+// Settings shared by every searcher.
+#[derive(Clone, Debug, Default)]
+pub struct SearchOptions {
+    // Don't expand world states which have already been expanded at the same depth or shallower.
+    pub graph_search: bool,
+    // Don't expand nodes beyond this depth.
+    pub max_depth: Option<u32>,
+    // Give up once this many nodes have been expanded.
+    pub node_budget: Option<u32>,
+    // Give up once the search has run for this long.
+    pub timeout: Option<Duration>,
+    // Give up once this is set, e.g. from another thread.
+    pub cancel: Option<Arc<AtomicBool>>,
+    // The order children are generated in.
+    pub direction_order: DirectionOrder,
+    // Seeds the shuffled direction order, so runs can be repeated. Random if not given.
+    pub seed: Option<usize>,
+    // Told about every node expanded, generated and pruned, e.g. to trace the search.
+    pub observer: Option<Rc<RefCell<dyn SearchObserver>>>,
+}
+impl SearchOptions {
+    // These options with only what is left of the node budget and timeout, for searchers which run
+    // several searches one after another: each search can only use what the earlier ones left.
+    fn remaining(&self, started: Instant, stats: &SearchStats) -> SearchOptions {
+        SearchOptions {
+            node_budget: self.node_budget
+                .map(|node_budget| node_budget.saturating_sub(stats.expanded_nodes)),
+            timeout: self.timeout.map(|timeout| timeout.saturating_sub(started.elapsed())),
+            ..self.clone()
+        }
+    }
+    fn notify(&self, event: SearchEvent) {
+        if let Some(ref observer) = self.observer {
+            observer.borrow_mut().observe(&event);
+        }
+    }
+    // Picks a random seed if none was given, returning the seed so it can be recorded with the results.
+    pub fn fix_seed(&mut self) -> usize {
+        *self.seed.get_or_insert_with(rand::random)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DirectionOrder {
+    // Shuffled for every expansion. For depth first especially, this reduces looping.
+    #[default]
+    Shuffled,
+    // Always up, down, left, right. Depth first tree search can then loop forever, so pair it with graph search.
+    Fixed,
+}
+
+// The node budget, timeout and cancel flag of a single search.
+struct SearchLimits {
+    node_budget: Option<u32>,
+    deadline: Option<Instant>,
+    cancel: Option<Arc<AtomicBool>>,
+}
+impl SearchLimits {
+    // The timeout starts counting from here.
+    fn new(options: &SearchOptions) -> SearchLimits {
+        SearchLimits {
+            node_budget: options.node_budget,
+            deadline: options.timeout.map(|timeout| Instant::now() + timeout),
+            cancel: options.cancel.clone(),
+        }
+    }
+    // Called before expanding each node, so that many nodes have been expanded so far.
+    fn check(&self, expanded_nodes: u32) -> Result<(), SearcherError> {
+        if self.node_budget.is_some_and(|node_budget| expanded_nodes >= node_budget) {
+            return Err(SearcherError::NodeBudgetExhaustedError);
+        }
+        if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Err(SearcherError::TimedOutError);
+        }
+        if self.cancel.as_ref().is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+            return Err(SearcherError::CancelledError);
+        }
+        Ok(())
+    }
+}
+
+// Gives the order to generate a node's children in, following the options.
+struct Directions {
+    order: DirectionOrder,
+    rng: StdRng,
+    directions: [world::Direction; 4],
+}
+impl Directions {
+    fn new(options: &SearchOptions) -> Directions {
+        Directions {
+            order: options.direction_order,
+            rng: match options.seed {
+                Some(seed) => StdRng::from_seed(&[seed][..]),
+                None => StdRng::new().unwrap(),
+            },
+            directions: world::Direction::directions_array(),
+        }
+    }
+    fn next(&mut self) -> [world::Direction; 4] {
+        if self.order == DirectionOrder::Shuffled {
+            self.rng.shuffle(&mut self.directions);
+        }
+        self.directions
+    }
+}
 
 pub trait Searcher {
     type NodeType: Node;
 
     // search() performs the main loop of a search operation
     // - pushing children to the fringe and popping the next node for checking.
-    // Returns either Ok(goal_node, expanded_nodes) or Err(error, expanded_nodes)
-    fn search(&mut self,
-              max_depth: Option<u32>)
-              -> Result<(Self::NodeType, u32), (SearcherError, u32)> {
-        let start_world_clone = self.get_start_world().clone();
-        let root_node = self.new_node(0, Box::new(start_world_clone), None);
-        self.fringe_push(root_node);
-
-        let mut expanded_nodes = 0;
-        let mut directions = world::Direction::directions_array();
-        loop {
-            let parent_rc = Rc::new(self.fringe_pop()
-                .ok_or((SearcherError::GoalNotFoundError, expanded_nodes))?);
-            if self.goal_reached(&*parent_rc) {
-                return match Rc::try_unwrap(parent_rc) {
-                    Ok(node) => Ok((node, expanded_nodes)),
-                    Err(_) => unreachable!(),
-                };
+    // Nodes beyond the options' max depth aren't expanded.
+    // Returns either Ok(goal_node, stats) or Err(error, stats)
+    fn search(&mut self) -> Result<(Self::NodeType, SearchStats), (SearcherError, SearchStats)> {
+        let started = Instant::now();
+        let mut stats = SearchStats::default();
+        let result = search_loop(self, &mut stats);
+        stats.elapsed = started.elapsed();
+        match result {
+            Ok(goal_node) => {
+                stats.solution_depth = Some(goal_node.get_depth());
+                stats.suboptimality_bound = self.suboptimality_bound();
+                Ok((goal_node, stats))
             }
-            let child_depth = parent_rc.get_depth() + 1;
-            match max_depth {
-                Some(max_depth) => {
-                    if child_depth > max_depth {
-                        continue;
-                    }
-                }
-                None => (),
-            }
-
-            thread_rng().shuffle(&mut directions); // For depth first especially, add children in a random order to reduce looping
-            for direction in directions.iter() {
-                parent_rc.get_world()
-                    .clone_and_move_agent(direction)
-                    .and_then(|new_world| {
-                        let new_node =
-                            self.new_node(child_depth,
-                                          Box::new(new_world),
-                                          Some(parent_rc.clone()));
-                        self.fringe_push(new_node);
-                        Ok(())
-                    })
-                    .ok();
-            }
-
-            expanded_nodes += 1
+            Err(error) => Err((error, stats)),
         }
     }
+    // How many times longer than optimal the plans found can be, given an admissible heuristic,
+    // or None if there's no bound. Reported in the stats of each plan found.
+    fn suboptimality_bound(&self) -> Option<f64> {
+        None
+    }
     fn goal_reached(&self, node: &Self::NodeType) -> bool {
         node.get_world().eq_ignore_agent(self.get_goal_world())// The agent location doesn't matter.
     }
 
+    // Graph search: maps each expanded world state to the shallowest depth it was expanded at.
+    // A state is only expanded again if it is reached at a smaller depth,
+    // which keeps depth limited searches complete.
+    // Searchers return None to perform a plain tree search.
+    fn explored_states(&mut self) -> Option<&mut HashMap<world::WorldState, u32>> {
+        None
+    }
+
     fn new_node(&self,
                 depth: u32,
-                world: Box<world::World>,
+                world: world::World,
                 parent: Option<Rc<Self::NodeType>>)
                 -> Self::NodeType;
+    fn get_options(&self) -> &SearchOptions;
     fn get_start_world(&self) -> &world::World;
     fn get_goal_world(&self) -> &world::World;
     fn fringe_push(&mut self, node: Self::NodeType);
     fn fringe_pop(&mut self) -> Option<Self::NodeType>;
+    fn fringe_len(&self) -> usize;
+    // Empties the fringe, along with anything kept about the nodes in it, so a searcher can search
+    // again. Called at the start of every search.
+    fn fringe_clear(&mut self);
+    // Nodes fringe_push has dropped since the search started, because their world state was already
+    // in the fringe as cheaply. Only searchers which check for that need to count them.
+    fn fringe_duplicates(&self) -> u32 {
+        0
+    }
+}
+
+fn search_loop<S: Searcher + ?Sized>(searcher: &mut S,
+                                     stats: &mut SearchStats)
+                                     -> Result<S::NodeType, SearcherError> {
+    searcher.fringe_clear();
+    if let Some(explored) = searcher.explored_states() {
+        explored.clear();
+    }
+    let root_node = searcher.new_node(0, searcher.get_start_world().clone(), None);
+    searcher.get_options().notify(SearchEvent::Generated {
+        world: root_node.get_world(),
+        depth: 0,
+    });
+    searcher.fringe_push(root_node);
+    stats.generated(0);
+
+    // Every node holds a copy of its world state, as does every entry of the explored set.
+    let state_size = state_size(searcher.get_start_world());
+    let node_size = mem::size_of::<S::NodeType>() + state_size;
+    let explored_size = state_size + mem::size_of::<u32>();
+
+    let limits = SearchLimits::new(searcher.get_options());
+    let mut directions = Directions::new(searcher.get_options());
+    let deadlocks = DeadlockDetector::new(searcher.get_goal_world());
+    let mut explored_duplicates = 0;
+    let result = loop {
+        let explored_len = searcher.explored_states().map_or(0, |explored| explored.len());
+        let fringe_len = searcher.fringe_len();
+        stats.fringe(fringe_len, fringe_len * node_size + explored_len * explored_size);
+
+        let parent_rc = match searcher.fringe_pop() {
+            Some(node) => Rc::new(node),
+            None => break Err(SearcherError::GoalNotFoundError),
+        };
+        if searcher.goal_reached(&parent_rc) {
+            searcher.get_options().notify(SearchEvent::GoalFound {
+                world: parent_rc.get_world(),
+                depth: parent_rc.get_depth(),
+            });
+            break match Rc::try_unwrap(parent_rc) {
+                Ok(node) => Ok(node),
+                Err(_) => unreachable!(),
+            };
+        }
+        let child_depth = parent_rc.get_depth() + 1;
+        if let Some(max_depth) = searcher.get_options().max_depth {
+            if child_depth > max_depth {
+                continue;
+            }
+        }
+        if let Some(explored) = searcher.explored_states() {
+            if !mark_explored(explored, parent_rc.get_world(), parent_rc.get_depth()) {
+                explored_duplicates += 1;
+                searcher.get_options().notify(SearchEvent::Pruned {
+                    world: parent_rc.get_world(),
+                    depth: parent_rc.get_depth(),
+                    reason: PruneReason::Duplicate,
+                });
+                continue;
+            }
+        }
+        if let Err(error) = limits.check(stats.expanded_nodes) {
+            break Err(error);
+        }
+        searcher.get_options().notify(SearchEvent::Expanded {
+            world: parent_rc.get_world(),
+            depth: parent_rc.get_depth(),
+        });
+
+        for direction in directions.next().iter() {
+            if let Ok(new_world) = parent_rc.get_world().clone_and_move_agent(direction) {
+                stats.generated(child_depth);
+                searcher.get_options().notify(SearchEvent::Generated {
+                    world: &new_world,
+                    depth: child_depth,
+                });
+                let is_duplicate = searcher.explored_states()
+                    .is_some_and(|explored| is_explored(explored, &new_world, child_depth));
+                if is_duplicate {
+                    explored_duplicates += 1;
+                    searcher.get_options().notify(SearchEvent::Pruned {
+                        world: &new_world,
+                        depth: child_depth,
+                        reason: PruneReason::Duplicate,
+                    });
+                    continue;
+                }
+                if deadlocks.is_deadlocked(&new_world) {
+                    stats.deadlocks_pruned += 1;
+                    searcher.get_options().notify(SearchEvent::Pruned {
+                        world: &new_world,
+                        depth: child_depth,
+                        reason: PruneReason::Deadlock,
+                    });
+                    continue;
+                }
+                let new_node = searcher.new_node(child_depth, new_world, Some(parent_rc.clone()));
+                searcher.fringe_push(new_node);
+            }
+        }
+
+        stats.expanded(parent_rc.get_depth());
+    };
+    stats.duplicates_pruned = explored_duplicates + searcher.fringe_duplicates();
+    result
 }
 
 pub trait Node {
@@ -90,50 +325,197 @@ pub trait Node {
         self.get_world().pretty_print();
         println!("{}", self.get_depth());
         let mut parent = self.get_parent();
-        loop {
-            match parent {
-                Some(node_rc) => {
-                    node_rc.get_world().pretty_print();
-                    println!("{}", node_rc.get_depth());
-                    parent = node_rc.get_parent();
-                }
-                None => break,
-            }
+        while let Some(node_rc) = parent {
+            node_rc.get_world().pretty_print();
+            println!("{}", node_rc.get_depth());
+            parent = node_rc.get_parent();
         }
     }
 }
 
+// The memory a copy of the world's state takes up, for estimating how much a search uses.
+fn state_size(world: &world::World) -> usize {
+    mem::size_of::<world::WorldState>() + world.state().heap_size()
+}
+
+fn is_explored(explored: &HashMap<world::WorldState, u32>, world: &world::World, depth: u32) -> bool {
+    explored.get(world.state()).is_some_and(|&explored_depth| explored_depth <= depth)
+}
+// Returns false if the world has already been expanded at this depth or shallower.
+fn mark_explored(explored: &mut HashMap<world::WorldState, u32>,
+                 world: &world::World,
+                 depth: u32)
+                 -> bool {
+    if is_explored(explored, world, depth) {
+        return false;
+    }
+    explored.insert(world.state().clone(), depth);
+    true
+}
+
 // A node implementing the most basic level of features.
 pub struct BasicNode {
     depth: u32,
-    world: Box<world::World>,
+    world: world::World,
     parent: Option<Rc<BasicNode>>,
 }
 impl BasicNode {
-    pub fn new(depth: u32, world: Box<world::World>, parent: Option<Rc<Self>>) -> Self {
+    pub fn new(depth: u32, world: world::World, parent: Option<Rc<Self>>) -> Self {
         BasicNode {
-            depth: depth,
-            world: world,
-            parent: parent,
+            depth,
+            world,
+            parent,
         }
     }
 }
 impl Node for BasicNode {
     fn get_world(&self) -> &world::World {
-        &*self.world
+        &self.world
     }
     fn get_depth(&self) -> u32 {
         self.depth
     }
     fn get_parent(&self) -> Option<Rc<BasicNode>> {
-        match &self.parent {
-            &Some(ref parent_rc) => Some(parent_rc.clone()),
-            &None => None,
+        self.parent.clone()
+    }
+}
+impl Drop for BasicNode {
+    // Dropping a node drops its parent, and so on up to the root. Unlink the chain one node at a time
+    // instead, so a search abandoned millions of moves deep doesn't overflow the stack.
+    // Stops at the first parent still shared with another node.
+    fn drop(&mut self) {
+        let mut parent = self.parent.take();
+        while let Some(Ok(mut node)) = parent.map(Rc::try_unwrap) {
+            parent = node.parent.take();
         }
     }
 }
 
+// Every error is returned alongside the SearchStats of what the search did before it stopped.
 #[derive(Debug)]
 pub enum SearcherError {
     GoalNotFoundError,
-}
\ No newline at end of file
+    NodeBudgetExhaustedError,
+    TimedOutError,
+    CancelledError,
+}
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+    use std::time::Duration;
+
+    use super::{BreadthFirstSearcher, DepthFirstSearcher, IdaStarSearcher,
+                IterativeDeepeningSearcher, SearchOptions, SearcherError};
+    use blocksworld::plan::Plan;
+    use blocksworld::puzzle::Puzzle;
+
+    fn readme_puzzle() -> Puzzle {
+        Puzzle::parse(include_str!("../../../puzzles/readme.txt")).unwrap()
+    }
+
+    #[test]
+    fn same_seed_repeats_search() {
+        let puzzle = readme_puzzle();
+        let options = SearchOptions {
+            graph_search: true,
+            seed: Some(42),
+            ..SearchOptions::default()
+        };
+        let search = || {
+            DepthFirstSearcher::new(puzzle.start.clone(), puzzle.goal.clone())
+                .with_options(options.clone())
+                .search()
+                .unwrap()
+        };
+        let (first_goal_node, first_stats) = search();
+        let (second_goal_node, second_stats) = search();
+        assert_eq!(Plan::from_node(&second_goal_node), Plan::from_node(&first_goal_node));
+        assert_eq!(second_stats.expanded_nodes, first_stats.expanded_nodes);
+        assert_eq!(second_stats.generated_nodes, first_stats.generated_nodes);
+    }
+
+    #[test]
+    fn fix_seed_keeps_given_seed() {
+        let mut options = SearchOptions {
+            seed: Some(42),
+            ..SearchOptions::default()
+        };
+        assert_eq!(options.fix_seed(), 42);
+        let mut options = SearchOptions::default();
+        let seed = options.fix_seed();
+        assert_eq!(options.seed, Some(seed));
+        assert_eq!(options.fix_seed(), seed);
+    }
+
+    #[test]
+    fn stops_at_node_budget() {
+        let puzzle = readme_puzzle();
+        let options = SearchOptions {
+            node_budget: Some(10),
+            ..SearchOptions::default()
+        };
+        match BreadthFirstSearcher::new(puzzle.start, puzzle.goal).with_options(options).search() {
+            Err((SearcherError::NodeBudgetExhaustedError, stats)) => {
+                assert_eq!(stats.expanded_nodes, 10)
+            }
+            result => panic!("unexpected result: {:?}", result.map(|(_, stats)| stats)),
+        }
+    }
+
+    #[test]
+    fn stops_at_timeout() {
+        let puzzle = readme_puzzle();
+        let options = SearchOptions {
+            timeout: Some(Duration::from_secs(0)),
+            ..SearchOptions::default()
+        };
+        match BreadthFirstSearcher::new(puzzle.start, puzzle.goal).with_options(options).search() {
+            Err((SearcherError::TimedOutError, stats)) => assert_eq!(stats.expanded_nodes, 0),
+            result => panic!("unexpected result: {:?}", result.map(|(_, stats)| stats)),
+        }
+    }
+
+    #[test]
+    fn stops_when_cancelled() {
+        let puzzle = readme_puzzle();
+        let options = SearchOptions {
+            cancel: Some(Arc::new(AtomicBool::new(true))),
+            ..SearchOptions::default()
+        };
+        match BreadthFirstSearcher::new(puzzle.start, puzzle.goal).with_options(options).search() {
+            Err((SearcherError::CancelledError, stats)) => assert_eq!(stats.expanded_nodes, 0),
+            result => panic!("unexpected result: {:?}", result.map(|(_, stats)| stats)),
+        }
+    }
+
+    // The budget is shared between iterations, so it runs out partway through a later one.
+    #[test]
+    fn iterative_searches_share_node_budget() {
+        let puzzle = readme_puzzle();
+        let options = SearchOptions {
+            node_budget: Some(1000),
+            ..SearchOptions::default()
+        };
+
+        let mut ida_star_searcher = IdaStarSearcher::new(puzzle.start.clone(), puzzle.goal.clone())
+            .with_options(options.clone());
+        match ida_star_searcher.search() {
+            Err((SearcherError::NodeBudgetExhaustedError, stats)) => {
+                assert_eq!(stats.expanded_nodes, 1000)
+            }
+            result => panic!("unexpected result: {:?}", result.map(|(_, stats)| stats)),
+        }
+        assert!(ida_star_searcher.iterations().len() > 1);
+
+        match IterativeDeepeningSearcher::new(puzzle.start, puzzle.goal)
+            .with_options(options)
+            .search() {
+            Err((SearcherError::NodeBudgetExhaustedError, stats)) => {
+                assert_eq!(stats.expanded_nodes, 1000);
+                assert!(stats.depths.len() > 2);
+            }
+            result => panic!("unexpected result: {:?}", result.map(|(_, stats)| stats)),
+        }
+    }
+}
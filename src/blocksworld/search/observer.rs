@@ -0,0 +1,160 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use ::blocksworld::world;
+
+// Watches a search as it runs, e.g. to report progress or record a trace.
+// Set one in SearchOptions::observer and every searcher run with those options reports to it,
+// including the searches run inside iterative deepening, IDA*, anytime, beam and optimal plans searches.
+pub trait SearchObserver: fmt::Debug {
+    fn observe(&mut self, event: &SearchEvent);
+}
+// Several observers can watch the same search, each seeing every event in turn.
+impl SearchObserver for Vec<Rc<RefCell<dyn SearchObserver>>> {
+    fn observe(&mut self, event: &SearchEvent) {
+        for observer in self.iter() {
+            observer.borrow_mut().observe(event);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SearchEvent<'a> {
+    // A node's children are about to be generated.
+    Expanded { world: &'a world::World, depth: u32 },
+    // A child was made by a valid move. It may still be pruned.
+    Generated { world: &'a world::World, depth: u32 },
+    // A node was dropped without being expanded.
+    Pruned { world: &'a world::World, depth: u32, reason: PruneReason },
+    GoalFound { world: &'a world::World, depth: u32 },
+    // Iterative deepening is starting a search with a deeper max depth.
+    DepthIncreased { max_depth: u32 },
+}
+impl<'a> SearchEvent<'a> {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SearchEvent::Expanded { .. } => "expanded",
+            SearchEvent::Generated { .. } => "generated",
+            SearchEvent::Pruned { .. } => "pruned",
+            SearchEvent::GoalFound { .. } => "goal_found",
+            SearchEvent::DepthIncreased { .. } => "depth_increased",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PruneReason {
+    // Its world state had already been reached or expanded as cheaply.
+    Duplicate,
+    // Its f(n) reached the cost bound or threshold, or the heuristic puts it too far from the goal.
+    CostBound,
+    // Beam search kept better nodes at its depth.
+    BeamWidth,
+    // Under push rules, a block got stuck where it can never reach its goal.
+    Deadlock,
+}
+impl PruneReason {
+    pub fn name(self) -> &'static str {
+        match self {
+            PruneReason::Duplicate => "duplicate",
+            PruneReason::CostBound => "cost_bound",
+            PruneReason::BeamWidth => "beam_width",
+            PruneReason::Deadlock => "deadlock",
+        }
+    }
+}
+
+// Writes every event as a line of JSON, for analysing or visualising searches offline:
+//   {"seq":3,"event":"generated","depth":1,"agent":[3,2],"blocks":{"A":[0,3],"B":[1,3]}}
+// Pruned events also have a "reason", and depth increases a "max_depth" instead of a world.
+// The first write error stops the trace, and is returned by finish.
+#[derive(Debug)]
+pub struct JsonTraceObserver<W: Write + fmt::Debug> {
+    writer: W,
+    seq: u64,
+    error: Option<io::Error>,
+}
+impl<W: Write + fmt::Debug> JsonTraceObserver<W> {
+    pub fn new(writer: W) -> JsonTraceObserver<W> {
+        JsonTraceObserver {
+            writer,
+            seq: 0,
+            error: None,
+        }
+    }
+    // Writes a line marking the start of a new search, so one trace can hold several.
+    pub fn start_search(&mut self, name: &str) {
+        let line = format!("{{\"seq\":{},\"event\":\"search\",\"name\":{}}}",
+                           self.seq,
+                           json_string(name));
+        self.write_line(&line);
+    }
+    // Flushes the trace, returning the writer or the first error writing it.
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(error) = writeln!(self.writer, "{}", line) {
+            self.error = Some(error);
+        }
+        self.seq += 1;
+    }
+}
+impl<W: Write + fmt::Debug> SearchObserver for JsonTraceObserver<W> {
+    fn observe(&mut self, event: &SearchEvent) {
+        let mut line = format!("{{\"seq\":{},\"event\":\"{}\"", self.seq, event.name());
+        match *event {
+            SearchEvent::Expanded { world, depth } |
+            SearchEvent::Generated { world, depth } |
+            SearchEvent::GoalFound { world, depth } => {
+                line += &format!(",\"depth\":{},{}", depth, json_world(world));
+            }
+            SearchEvent::Pruned { world, depth, reason } => {
+                line += &format!(",\"depth\":{},\"reason\":\"{}\",{}",
+                                 depth,
+                                 reason.name(),
+                                 json_world(world));
+            }
+            SearchEvent::DepthIncreased { max_depth } => {
+                line += &format!(",\"max_depth\":{}", max_depth);
+            }
+        }
+        line.push('}');
+        self.write_line(&line);
+    }
+}
+
+// The agent's location and each block's, as JSON object members.
+fn json_world(world: &world::World) -> String {
+    let agent = world.get_entity_location(&world::Entity::Agent).unwrap();
+    let blocks = world.blocks()
+        .map(|(block_char, loc)| {
+            format!("{}:[{},{}]", json_string(&block_char.to_string()), loc.x(), loc.y())
+        })
+        .collect::<Vec<String>>();
+    format!("\"agent\":[{},{}],\"blocks\":{{{}}}", agent.x(), agent.y(), blocks.join(","))
+}
+
+fn json_string(text: &str) -> String {
+    let mut json = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => json += "\\\"",
+            '\\' => json += "\\\\",
+            c if (c as u32) < 0x20 => json += &format!("\\u{:04x}", c as u32),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
@@ -0,0 +1,349 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::time::Instant;
+
+use super::AStarSearcher;
+use super::BasicNode;
+use super::DeadlockDetector;
+use super::Directions;
+use super::{Heuristic, ManhattanHeuristic};
+use super::Node;
+use super::{PruneReason, SearchEvent};
+use super::SearchLimits;
+use super::SearchOptions;
+use super::state_size;
+use super::SearchStats;
+use super::SearcherError;
+use ::blocksworld::world;
+
+// Finds every optimal plan rather than just one.
+// A* finds the optimal number of moves first, then a breadth first search to that depth records
+// every way of reaching each world in one move from the layer before, skipping worlds the heuristic
+// says are too far from the goal to be on an optimal plan.
+// The plans found are only all of the optimal plans if the heuristic is admissible.
+pub struct OptimalPlansSearcher {
+    start_world: world::World,
+    goal_world: world::World,
+    options: SearchOptions,
+    heuristic: Rc<dyn Heuristic>,
+}
+impl OptimalPlansSearcher {
+    pub fn new(start_world: world::World, goal_world: world::World) -> OptimalPlansSearcher {
+        OptimalPlansSearcher {
+            start_world,
+            goal_world,
+            options: SearchOptions::default(),
+            heuristic: Rc::new(ManhattanHeuristic),
+        }
+    }
+    pub fn with_options(mut self, options: SearchOptions) -> OptimalPlansSearcher {
+        self.options = options;
+        self
+    }
+    pub fn with_heuristic<H: Heuristic + 'static>(mut self, heuristic: H) -> OptimalPlansSearcher {
+        self.heuristic = Rc::new(heuristic);
+        self
+    }
+    // Returns the optimal plans, which can be counted without listing them,
+    // with the stats of both searches added up.
+    pub fn search(&mut self) -> Result<(OptimalPlans, SearchStats), (SearcherError, SearchStats)> {
+        let started = Instant::now();
+        let (goal_node, mut stats) = AStarSearcher::new(self.start_world.clone(),
+                                                        self.goal_world.clone())
+            .with_options(self.options.clone())
+            .with_heuristic(self.heuristic.clone())
+            .search()?;
+        let moves = goal_node.get_depth();
+
+        let options = self.options.remaining(started, &stats);
+        let mut layer_stats = SearchStats::default();
+        let result = self.search_layers(moves, &options, &mut layer_stats);
+        stats.add(&layer_stats);
+        stats.elapsed = started.elapsed();
+        match result {
+            Ok(optimal_plans) => Ok((optimal_plans, stats)),
+            Err(error) => {
+                stats.solution_depth = None;
+                stats.suboptimality_bound = None;
+                Err((error, stats))
+            }
+        }
+    }
+
+    fn search_layers(&self,
+                     moves: u32,
+                     options: &SearchOptions,
+                     stats: &mut SearchStats)
+                     -> Result<OptimalPlans, SearcherError> {
+        let limits = SearchLimits::new(options);
+        let mut directions = Directions::new(options);
+        let deadlocks = DeadlockDetector::new(&self.goal_world);
+        // Each state is held in its layer and as a key of the depths and predecessors maps,
+        // and each move between layers as a predecessor.
+        let state_size = state_size(&self.start_world);
+
+        let start_state = self.start_world.state().clone();
+        let mut depths = HashMap::new();
+        depths.insert(start_state.clone(), 0);
+        let mut predecessors: HashMap<world::WorldState, Vec<world::WorldState>> = HashMap::new();
+        let mut layers = vec![vec![start_state]];
+        let mut moves_between_layers = 0;
+        stats.generated(0);
+        options.notify(SearchEvent::Generated {
+            world: &self.start_world,
+            depth: 0,
+        });
+        for depth in 0..moves {
+            let mut next_layer = Vec::new();
+            for state in &layers[depth as usize] {
+                limits.check(stats.expanded_nodes)?;
+                let world = self.start_world.with_state(state.clone());
+                options.notify(SearchEvent::Expanded {
+                    world: &world,
+                    depth,
+                });
+                for direction in directions.next().iter() {
+                    let child = match world.clone_and_move_agent(direction) {
+                        Ok(child) => child,
+                        Err(_) => continue,
+                    };
+                    let child_depth = depth + 1;
+                    stats.generated(child_depth);
+                    options.notify(SearchEvent::Generated {
+                        world: &child,
+                        depth: child_depth,
+                    });
+                    if child_depth as usize + self.heuristic.estimate(&child, &self.goal_world) >
+                       moves as usize {
+                        options.notify(SearchEvent::Pruned {
+                            world: &child,
+                            depth: child_depth,
+                            reason: PruneReason::CostBound,
+                        });
+                        continue;
+                    }
+                    if deadlocks.is_deadlocked(&child) {
+                        stats.deadlocks_pruned += 1;
+                        options.notify(SearchEvent::Pruned {
+                            world: &child,
+                            depth: child_depth,
+                            reason: PruneReason::Deadlock,
+                        });
+                        continue;
+                    }
+                    match depths.get(child.state()).cloned() {
+                        Some(explored_depth) if explored_depth < child_depth => {
+                            stats.duplicates_pruned += 1;
+                            options.notify(SearchEvent::Pruned {
+                                world: &child,
+                                depth: child_depth,
+                                reason: PruneReason::Duplicate,
+                            });
+                            continue;
+                        }
+                        // Another way of reaching a world already in the next layer.
+                        Some(_) => {}
+                        None => {
+                            depths.insert(child.state().clone(), child_depth);
+                            next_layer.push(child.state().clone());
+                        }
+                    }
+                    predecessors.entry(child.state().clone()).or_default().push(state.clone());
+                    moves_between_layers += 1;
+                }
+                stats.expanded(depth);
+            }
+            stats.fringe(next_layer.len(),
+                         (3 * depths.len() + moves_between_layers) * state_size);
+            layers.push(next_layer);
+        }
+
+        let mut goals = Vec::new();
+        for state in &layers[moves as usize] {
+            let world = self.start_world.with_state(state.clone());
+            if world.eq_ignore_agent(&self.goal_world) {
+                options.notify(SearchEvent::GoalFound {
+                    world: &world,
+                    depth: moves,
+                });
+                goals.push(state.clone());
+            }
+        }
+        Ok(OptimalPlans {
+            start_world: self.start_world.clone(),
+            moves,
+            layers,
+            predecessors,
+            goals,
+        })
+    }
+}
+
+// Every optimal plan from the start world to the goal, as the layers of the breadth first search
+// and the moves between them.
+pub struct OptimalPlans {
+    start_world: world::World,
+    moves: u32,
+    layers: Vec<Vec<world::WorldState>>,
+    predecessors: HashMap<world::WorldState, Vec<world::WorldState>>,
+    goals: Vec<world::WorldState>,
+}
+impl OptimalPlans {
+    // The number of moves in each plan.
+    pub fn moves(&self) -> u32 {
+        self.moves
+    }
+    // The number of optimal plans, counted without listing them.
+    // Saturates at u128::MAX, which only plans more than 64 moves long could reach.
+    pub fn count(&self) -> u128 {
+        let mut counts = HashMap::new();
+        counts.insert(self.start_world.state(), 1u128);
+        for layer in self.layers.iter().skip(1) {
+            for state in layer {
+                let count = self.predecessors[state]
+                    .iter()
+                    .fold(0u128, |count, predecessor| count.saturating_add(counts[predecessor]));
+                counts.insert(state, count);
+            }
+        }
+        self.goals.iter().fold(0, |count, goal| count.saturating_add(counts[goal]))
+    }
+    // The goal node of each optimal plan, up to limit of them.
+    // Plans share nodes for the moves they have in common.
+    pub fn plans(&self, limit: Option<usize>) -> Vec<BasicNode> {
+        let limit = limit.unwrap_or(usize::MAX);
+        if self.moves == 0 || limit == 0 {
+            let root = BasicNode::new(0, self.start_world.clone(), None);
+            return if limit == 0 { Vec::new() } else { vec![root] };
+        }
+
+        // Only follow moves which lead on to a goal.
+        let mut on_plan: HashSet<&world::WorldState> = self.goals.iter().collect();
+        let mut stack: Vec<&world::WorldState> = self.goals.iter().collect();
+        while let Some(state) = stack.pop() {
+            for predecessor in self.predecessors.get(state).into_iter().flatten() {
+                if on_plan.insert(predecessor) {
+                    stack.push(predecessor);
+                }
+            }
+        }
+        let mut successors: HashMap<&world::WorldState, Vec<&world::WorldState>> = HashMap::new();
+        for state in self.layers.iter().flatten().filter(|state| on_plan.contains(state)) {
+            for predecessor in self.predecessors.get(state).into_iter().flatten() {
+                successors.entry(predecessor).or_default().push(state);
+            }
+        }
+
+        let mut plans = Vec::new();
+        let mut stack = vec![Rc::new(BasicNode::new(0, self.start_world.clone(), None))];
+        while let Some(node_rc) = stack.pop() {
+            let depth = node_rc.get_depth() + 1;
+            for &state in successors.get(node_rc.get_world().state()).into_iter().flatten() {
+                let node = BasicNode::new(depth,
+                                          self.start_world.with_state(state.clone()),
+                                          Some(node_rc.clone()));
+                if depth < self.moves {
+                    stack.push(Rc::new(node));
+                    continue;
+                }
+                plans.push(node);
+                if plans.len() >= limit {
+                    return plans;
+                }
+            }
+        }
+        plans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    use super::OptimalPlansSearcher;
+    use blocksworld::search::{Node, PruneReason, SearchEvent, SearchObserver, SearchOptions};
+    use blocksworld::test::fixtures::{test_puzzles, TestPuzzle};
+    use blocksworld::world::{MoveRules, WorldState};
+
+    // How many optimal plans each test puzzle has under each set of rules.
+    const COUNTS: [(&str, MoveRules, u128); 7] = [("readme", MoveRules::Swap, 1),
+                                                  ("readme", MoveRules::PushPull, 2),
+                                                  ("rooms_6x4", MoveRules::Swap, 1),
+                                                  ("warehouse_6x5", MoveRules::Swap, 2),
+                                                  ("warehouse_6x5", MoveRules::Push, 4),
+                                                  ("warehouse_6x5", MoveRules::PushPull, 3),
+                                                  ("tower_5x5", MoveRules::Swap, 2)];
+
+    #[test]
+    fn finds_every_optimal_plan() {
+        for TestPuzzle { name, puzzle, optimal_length } in test_puzzles() {
+            let move_rules = puzzle.goal.move_rules();
+            let &(_, _, count) = COUNTS.iter()
+                .find(|&&(count_name, count_rules, _)| {
+                    count_name == name && count_rules == move_rules
+                })
+                .unwrap();
+            let (optimal_plans, _) = OptimalPlansSearcher::new(puzzle.start, puzzle.goal)
+                .search()
+                .unwrap();
+            let description = format!("{} under {} rules", name, move_rules.name());
+            assert_eq!(optimal_plans.moves(), optimal_length, "{}", description);
+            assert_eq!(optimal_plans.count(), count, "{}", description);
+            let plans = optimal_plans.plans(None);
+            assert_eq!(plans.len() as u128, count, "{}", description);
+            assert!(plans.iter().all(|plan| plan.get_depth() == optimal_length));
+        }
+    }
+
+    // Watches the breadth first search to the optimal depth, which starts after A* finds the goal.
+    #[derive(Debug, Default)]
+    struct LayerObserver {
+        astar_done: bool,
+        astar_duplicates: u32,
+        first_depths: HashMap<WorldState, u32>,
+        // The depth each pruned duplicate was reached at, and the depth it was first reached at.
+        duplicates: Vec<(u32, u32)>,
+    }
+    impl SearchObserver for LayerObserver {
+        fn observe(&mut self, event: &SearchEvent) {
+            match *event {
+                SearchEvent::GoalFound { .. } => self.astar_done = true,
+                SearchEvent::Pruned { reason: PruneReason::Duplicate, .. } if !self.astar_done => {
+                    self.astar_duplicates += 1;
+                }
+                SearchEvent::Generated { world, depth } if self.astar_done => {
+                    self.first_depths.entry(world.state().clone()).or_insert(depth);
+                }
+                SearchEvent::Pruned { world, depth, reason: PruneReason::Duplicate } => {
+                    self.duplicates.push((depth, self.first_depths[world.state()]));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn only_prunes_duplicates_reached_deeper() {
+        for TestPuzzle { name, puzzle, .. } in test_puzzles() {
+            let observer = Rc::new(RefCell::new(LayerObserver::default()));
+            let options = SearchOptions {
+                observer: Some(observer.clone()),
+                ..SearchOptions::default()
+            };
+            let (_, stats) = OptimalPlansSearcher::new(puzzle.start, puzzle.goal)
+                .with_options(options)
+                .search()
+                .unwrap();
+            let observer = observer.borrow();
+            assert!(observer.duplicates.iter().all(|&(depth, first_depth)| depth > first_depth),
+                    "{}",
+                    name);
+            assert_eq!(observer.astar_duplicates + observer.duplicates.len() as u32,
+                       stats.duplicates_pruned,
+                       "{}",
+                       name);
+        }
+    }
+}
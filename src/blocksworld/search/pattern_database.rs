@@ -0,0 +1,496 @@
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use super::{Heuristic, MaxHeuristic};
+use ::blocksworld::world;
+
+// Exact move counts to the goal for every arrangement of the agent and a subset of the blocks,
+// found by searching backwards from the goal over worlds holding only those blocks.
+// Removing blocks can only make the puzzle easier (swapping with a removed block becomes a move into
+// an empty cell), so these counts never overestimate the real puzzle. The same goes for push rules:
+// a push into a removed block's cell would have been blocked, and a pull of one doesn't happen.
+pub struct PatternDatabase {
+    width: usize,
+    height: usize,
+    blocks: Vec<char>,
+    // The goal cell of each block in the subset, so a database is never used against the wrong goal.
+    goal_cells: Vec<usize>,
+    // The cells of the walls, which the distances depend on too.
+    wall_cells: Vec<usize>,
+    move_rules: world::MoveRules,
+    // Indexed by the cells of the agent and each block in the subset, see index().
+    distances: Vec<u8>,
+}
+impl PatternDatabase {
+    // Tables are indexed by every combination of cells, so are limited to this many entries.
+    pub const MAX_ENTRIES: usize = 1 << 26;
+    // Tables this size take around a second to build.
+    pub const DEFAULT_ENTRIES: usize = 1 << 22;
+    // Marks arrangements the search never reached, which can't reach the goal.
+    const UNREACHED: u8 = u8::MAX;
+    // Starts every saved database, with a version number in case the format changes.
+    // Version 2 added walls, and version 3 move rules.
+    const MAGIC: &'static [u8; 8] = b"BWPDB\0\0\x03";
+
+    pub fn build(goal_world: &world::World,
+                 block_chars: &[char])
+                 -> Result<PatternDatabase, PatternDatabaseError> {
+        let mut blocks = block_chars.to_vec();
+        blocks.sort();
+        blocks.dedup();
+        let entries = goal_world.cell_count()
+            .checked_pow(blocks.len() as u32 + 1)
+            .filter(|&entries| entries <= PatternDatabase::MAX_ENTRIES)
+            .ok_or(PatternDatabaseError::TooLargeError)?;
+        let abstract_goal = goal_world.with_only_blocks(&blocks)
+            .map_err(|_| PatternDatabaseError::NonExistentBlockError)?;
+
+        let mut database = PatternDatabase {
+            width: goal_world.width(),
+            height: goal_world.height(),
+            goal_cells: blocks.iter()
+                .map(|&block_char| {
+                    abstract_goal.get_entity_cell(&world::Entity::Block(block_char)).unwrap()
+                })
+                .collect(),
+            wall_cells: wall_cells(goal_world).collect(),
+            move_rules: goal_world.move_rules(),
+            blocks,
+            distances: vec![PatternDatabase::UNREACHED; entries],
+        };
+
+        // Breadth first from every goal arrangement at once, since the agent can finish anywhere.
+        let mut fringe = VecDeque::new();
+        for world in abstract_goal.agent_placements() {
+            let index = database.index(&world);
+            database.distances[index] = 0;
+            fringe.push_back(world);
+        }
+        while let Some(world) = fringe.pop_front() {
+            // Longer distances are capped, which still never overestimates.
+            let distance = database.distances[database.index(&world)]
+                .saturating_add(1)
+                .min(PatternDatabase::UNREACHED - 1);
+            for direction in world::Direction::directions_array().iter() {
+                for previous_world in world.previous_worlds(direction) {
+                    let index = database.index(&previous_world);
+                    if database.distances[index] == PatternDatabase::UNREACHED {
+                        database.distances[index] = distance;
+                        fringe.push_back(previous_world);
+                    }
+                }
+            }
+        }
+        Ok(database)
+    }
+    // Reads a database written by save().
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<PatternDatabase, PatternDatabaseError> {
+        PatternDatabase::read_from(&mut BufReader::new(File::open(path)?))
+    }
+    // Writes the database in a compact binary format: a header describing the grid, blocks and
+    // goal, walls and move rules, followed by one byte per distance.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), PatternDatabaseError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+    pub fn blocks(&self) -> &[char] {
+        &self.blocks
+    }
+    // Whether the database was built for a goal world of this size, walls and move rules, with
+    // these blocks in the same places.
+    // Checked for every estimate, so this doesn't allocate.
+    pub fn is_built_for(&self, goal_world: &world::World) -> bool {
+        goal_world.width() == self.width && goal_world.height() == self.height &&
+        goal_world.move_rules() == self.move_rules &&
+        wall_cells(goal_world).eq(self.wall_cells.iter().cloned()) &&
+        self.blocks.iter().zip(self.goal_cells.iter()).all(|(&block_char, &goal_cell)| {
+            goal_world.get_entity_cell(&world::Entity::Block(block_char)).ok() == Some(goal_cell)
+        })
+    }
+    fn matches(&self, world: &world::World, goal_world: &world::World) -> bool {
+        self.is_built_for(goal_world) && world.width() == self.width &&
+        world.height() == self.height &&
+        self.blocks.iter().all(|&block_char| {
+            world.get_entity_cell(&world::Entity::Block(block_char)).is_ok()
+        })
+    }
+    // The agent's cell followed by each block's, read as the digits of a number in base cell_count.
+    fn index(&self, world: &world::World) -> usize {
+        let cell_count = self.width * self.height;
+        self.blocks.iter().fold(world.get_entity_cell(&world::Entity::Agent).unwrap(),
+                                |index, &block_char| {
+            index * cell_count + world.get_entity_cell(&world::Entity::Block(block_char)).unwrap()
+        })
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(PatternDatabase::MAGIC)?;
+        write_u32(writer, self.width as u32)?;
+        write_u32(writer, self.height as u32)?;
+        write_u32(writer, self.blocks.len() as u32)?;
+        for (&block_char, &goal_cell) in self.blocks.iter().zip(self.goal_cells.iter()) {
+            write_u32(writer, block_char as u32)?;
+            write_u32(writer, goal_cell as u32)?;
+        }
+        write_u32(writer, self.wall_cells.len() as u32)?;
+        for &wall_cell in &self.wall_cells {
+            write_u32(writer, wall_cell as u32)?;
+        }
+        let move_rules = MOVE_RULES.iter().position(|&rules| rules == self.move_rules).unwrap();
+        write_u32(writer, move_rules as u32)?;
+        writer.write_all(&self.distances)
+    }
+    fn read_from<R: Read>(reader: &mut R) -> Result<PatternDatabase, PatternDatabaseError> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != PatternDatabase::MAGIC {
+            return Err(PatternDatabaseError::InvalidFileError);
+        }
+        let width = read_u32(reader)? as usize;
+        let height = read_u32(reader)? as usize;
+        let block_count = read_u32(reader)? as usize;
+        let cell_count = width * height;
+        let entries = cell_count.checked_pow(block_count as u32 + 1)
+            .filter(|&entries| entries <= PatternDatabase::MAX_ENTRIES)
+            .ok_or(PatternDatabaseError::InvalidFileError)?;
+
+        let mut blocks = Vec::with_capacity(block_count);
+        let mut goal_cells = Vec::with_capacity(block_count);
+        for _ in 0..block_count {
+            let block_char = std::char::from_u32(read_u32(reader)?)
+                .ok_or(PatternDatabaseError::InvalidFileError)?;
+            let goal_cell = read_u32(reader)? as usize;
+            // Blocks are stored sorted, which index() relies on.
+            if blocks.last().is_some_and(|&last_char| last_char >= block_char) ||
+               goal_cell >= cell_count {
+                return Err(PatternDatabaseError::InvalidFileError);
+            }
+            blocks.push(block_char);
+            goal_cells.push(goal_cell);
+        }
+        let wall_count = read_u32(reader)? as usize;
+        if wall_count > cell_count {
+            return Err(PatternDatabaseError::InvalidFileError);
+        }
+        let mut wall_cells = Vec::with_capacity(wall_count);
+        for _ in 0..wall_count {
+            let wall_cell = read_u32(reader)? as usize;
+            // Stored in cell order, as wall_cells() lists them.
+            if wall_cells.last().is_some_and(|&last_cell| last_cell >= wall_cell) ||
+               wall_cell >= cell_count {
+                return Err(PatternDatabaseError::InvalidFileError);
+            }
+            wall_cells.push(wall_cell);
+        }
+        let move_rules = *MOVE_RULES.get(read_u32(reader)? as usize)
+            .ok_or(PatternDatabaseError::InvalidFileError)?;
+        let mut distances = vec![0; entries];
+        reader.read_exact(&mut distances)?;
+        if reader.read(&mut [0])? != 0 {
+            return Err(PatternDatabaseError::InvalidFileError);
+        }
+
+        Ok(PatternDatabase {
+            width,
+            height,
+            blocks,
+            goal_cells,
+            wall_cells,
+            move_rules,
+            distances,
+        })
+    }
+}
+
+impl Heuristic for PatternDatabase {
+    // Worlds the database wasn't built for (a different size, goal or set of blocks) get an estimate of 0.
+    fn estimate(&self, world: &world::World, goal_world: &world::World) -> usize {
+        if !self.matches(world, goal_world) {
+            return 0;
+        }
+        // Unreachable arrangements can never reach the goal, so any estimate of them is admissible.
+        usize::from(self.distances[self.index(world)])
+    }
+}
+
+// Move rules are saved as their position in here.
+const MOVE_RULES: [world::MoveRules; 3] = [world::MoveRules::Swap,
+                                           world::MoveRules::Push,
+                                           world::MoveRules::PushPull];
+
+// The cell of each of a world's walls, in cell order.
+fn wall_cells<'a>(world: &'a world::World) -> impl Iterator<Item = usize> + 'a {
+    world.walls().map(move |location| location.y() as usize * world.width() + location.x() as usize)
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+// Builds a pattern database for each group of a goal world's blocks.
+// With a cache directory, databases saved there by an earlier run are loaded instead of being rebuilt,
+// and newly built ones are saved for next time.
+pub struct PatternDatabaseBuilder {
+    goal_world: world::World,
+    group_size: Option<usize>,
+    cache_dir: Option<PathBuf>,
+}
+impl PatternDatabaseBuilder {
+    pub fn new(goal_world: &world::World) -> PatternDatabaseBuilder {
+        PatternDatabaseBuilder {
+            goal_world: goal_world.clone(),
+            group_size: None,
+            cache_dir: None,
+        }
+    }
+    // Blocks per database. By default each group is as large as possible (up to 3 blocks)
+    // while its table stays under PatternDatabase::DEFAULT_ENTRIES.
+    pub fn with_group_size(mut self, group_size: usize) -> PatternDatabaseBuilder {
+        self.group_size = Some(group_size);
+        self
+    }
+    pub fn with_cache_dir<P: AsRef<Path>>(mut self, cache_dir: P) -> PatternDatabaseBuilder {
+        self.cache_dir = Some(cache_dir.as_ref().to_path_buf());
+        self
+    }
+    pub fn build(&self) -> Result<Vec<PatternDatabase>, PatternDatabaseError> {
+        let blocks = self.goal_world
+            .blocks()
+            .map(|(block_char, _)| block_char)
+            .collect::<Vec<char>>();
+        let group_size = match self.group_size {
+            Some(group_size) => group_size.max(1),
+            None => self.default_group_size()?,
+        };
+        blocks.chunks(group_size).map(|group| self.build_group(group)).collect()
+    }
+    // The max of every database built, to use with A*.
+    pub fn build_heuristic(&self) -> Result<MaxHeuristic, PatternDatabaseError> {
+        self.build().map(|databases| {
+            databases.into_iter().fold(MaxHeuristic::new(), |max, database| max.with(database))
+        })
+    }
+
+    fn default_group_size(&self) -> Result<usize, PatternDatabaseError> {
+        (1..4)
+            .rev()
+            .find(|&group_size| {
+                self.goal_world
+                    .cell_count()
+                    .checked_pow(group_size as u32 + 1)
+                    .is_some_and(|entries| entries <= PatternDatabase::DEFAULT_ENTRIES)
+            })
+            .ok_or(PatternDatabaseError::TooLargeError)
+    }
+    fn build_group(&self, group: &[char]) -> Result<PatternDatabase, PatternDatabaseError> {
+        let cache_path = match self.cache_dir {
+            Some(ref cache_dir) => cache_dir.join(self.cache_file_name(group)),
+            None => return PatternDatabase::build(&self.goal_world, group),
+        };
+        // A missing, unreadable or mismatched cache file is just rebuilt and overwritten.
+        if let Ok(database) = PatternDatabase::load(&cache_path) {
+            if database.is_built_for(&self.goal_world) && database.blocks() == group {
+                return Ok(database);
+            }
+        }
+        let database = PatternDatabase::build(&self.goal_world, group)?;
+        fs::create_dir_all(cache_path.parent().unwrap())?;
+        database.save(&cache_path)?;
+        Ok(database)
+    }
+    // Named after everything the database depends on, e.g. "4x4_ABC_5-9-13.pdb", with a hash of the
+    // wall cells if there are any, e.g. "4x4_ABC_5-9-13_walls-8c3f0a1b2d4e5f60.pdb", and the move
+    // rules unless they're swaps, e.g. "4x4_ABC_5-9-13_push.pdb".
+    // The hash is FNV-1a, which unlike the standard library's hasher stays the same between builds.
+    fn cache_file_name(&self, group: &[char]) -> String {
+        let goal_cells = group.iter()
+            .map(|&block_char| {
+                let goal_cell = self.goal_world.get_entity_cell(&world::Entity::Block(block_char));
+                goal_cell.unwrap().to_string()
+            })
+            .collect::<Vec<String>>();
+        let wall_cells = wall_cells(&self.goal_world).collect::<Vec<usize>>();
+        let walls = if wall_cells.is_empty() {
+            String::new()
+        } else {
+            let hash = wall_cells.iter()
+                .flat_map(|&wall_cell| (wall_cell as u32).to_le_bytes())
+                .fold(0xcbf29ce484222325u64,
+                      |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+            format!("_walls-{:016x}", hash)
+        };
+        let move_rules = match self.goal_world.move_rules() {
+            world::MoveRules::Swap => String::new(),
+            move_rules => format!("_{}", move_rules.name()),
+        };
+        format!("{}x{}_{}_{}{}{}.pdb",
+                self.goal_world.width(),
+                self.goal_world.height(),
+                group.iter().collect::<String>(),
+                goal_cells.join("-"),
+                walls,
+                move_rules)
+    }
+}
+
+#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum PatternDatabaseError {
+    TooLargeError,
+    NonExistentBlockError,
+    IoError(io::Error),
+    // The file isn't a pattern database, or is corrupt.
+    InvalidFileError,
+}
+impl From<io::Error> for PatternDatabaseError {
+    fn from(error: io::Error) -> PatternDatabaseError {
+        PatternDatabaseError::IoError(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+
+    use super::{PatternDatabase, PatternDatabaseBuilder, PatternDatabaseError};
+    use blocksworld::puzzle::Puzzle;
+    use blocksworld::world::{MoveRules, World};
+
+    const GOAL: &str = "\
+* * * * * *
+*   A     *
+*       @ *
+*   B C   *
+* * * * * *";
+    // GOAL with a wall in the middle row.
+    const WALLED_GOAL: &str = "\
+* * * * * *
+*   A     *
+*   *   @ *
+*   B C   *
+* * * * * *";
+
+    fn goal_world(grid: &str) -> World {
+        Puzzle::parse(&format!("{}\n\n{}\n", grid, grid)).unwrap().goal
+    }
+
+    // An empty directory for a test to write to, removed again when the test is done with it.
+    struct TestDir(PathBuf);
+    impl TestDir {
+        fn new(name: &str) -> TestDir {
+            let path = env::temp_dir()
+                .join(format!("blocksworld_pattern_database_{}_{}", name, process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TestDir(path)
+        }
+    }
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn assert_same(database: &PatternDatabase, other: &PatternDatabase) {
+        assert_eq!((database.width, database.height), (other.width, other.height));
+        assert_eq!(database.blocks, other.blocks);
+        assert_eq!(database.goal_cells, other.goal_cells);
+        assert_eq!(database.wall_cells, other.wall_cells);
+        assert_eq!(database.move_rules, other.move_rules);
+        assert!(database.distances == other.distances);
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let dir = TestDir::new("round_trip");
+        for &move_rules in [MoveRules::Swap, MoveRules::Push, MoveRules::PushPull].iter() {
+            let goal_world = goal_world(WALLED_GOAL).with_move_rules(move_rules);
+            let database = PatternDatabase::build(&goal_world, &['A', 'C']).unwrap();
+            let path = dir.0.join(format!("{}.pdb", move_rules.name()));
+            database.save(&path).unwrap();
+            let loaded = PatternDatabase::load(&path).unwrap();
+            assert_same(&loaded, &database);
+            assert!(loaded.is_built_for(&goal_world));
+        }
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        let dir = TestDir::new("bad_files");
+        let database = PatternDatabase::build(&goal_world(WALLED_GOAL), &['A', 'B']).unwrap();
+        let mut bytes = Vec::new();
+        database.write_to(&mut bytes).unwrap();
+        let load = |name: &str, bytes: &[u8]| {
+            let path = dir.0.join(name);
+            fs::write(&path, bytes).unwrap();
+            PatternDatabase::load(&path)
+        };
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        match load("bad_magic.pdb", &bad_magic) {
+            Err(PatternDatabaseError::InvalidFileError) => {}
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        }
+        match load("truncated.pdb", &bytes[..bytes.len() - 1]) {
+            Err(PatternDatabaseError::IoError(_)) => {}
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        }
+        match load("truncated_header.pdb", &bytes[..10]) {
+            Err(PatternDatabaseError::IoError(_)) => {}
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        }
+        let mut too_long = bytes.clone();
+        too_long.push(0);
+        match load("too_long.pdb", &too_long) {
+            Err(PatternDatabaseError::InvalidFileError) => {}
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        }
+    }
+
+    // Copies a cache file built for one goal to the name the builder for another goal looks for,
+    // then checks that builder rebuilds the database for its own goal instead of using it.
+    fn assert_mismatched_cache_rebuilt(cached_goal_world: &World, goal_world: &World, name: &str) {
+        let dir = TestDir::new(name);
+        let cached_builder = PatternDatabaseBuilder::new(cached_goal_world).with_cache_dir(&dir.0);
+        let builder = PatternDatabaseBuilder::new(goal_world).with_cache_dir(&dir.0);
+        let group = ['A', 'B', 'C'];
+        let cached_path = dir.0.join(cached_builder.cache_file_name(&group));
+        let path = dir.0.join(builder.cache_file_name(&group));
+        assert!(path != cached_path);
+
+        cached_builder.build().unwrap();
+        fs::copy(&cached_path, &path).unwrap();
+        let databases = builder.build().unwrap();
+        assert_eq!(databases.len(), 1);
+        assert!(databases[0].is_built_for(goal_world));
+        assert_same(&databases[0], &PatternDatabase::build(goal_world, &group).unwrap());
+        // The mismatched file is overwritten with the rebuilt database.
+        assert!(PatternDatabase::load(&path).unwrap().is_built_for(goal_world));
+    }
+
+    #[test]
+    fn rebuilds_cache_for_different_walls() {
+        assert_mismatched_cache_rebuilt(&goal_world(GOAL), &goal_world(WALLED_GOAL), "walls");
+    }
+
+    #[test]
+    fn rebuilds_cache_for_different_move_rules() {
+        let goal_world = goal_world(WALLED_GOAL);
+        assert_mismatched_cache_rebuilt(&goal_world,
+                                        &goal_world.clone().with_move_rules(MoveRules::Push),
+                                        "move_rules");
+    }
+}
@@ -0,0 +1,124 @@
+use std::fmt;
+use std::time::Duration;
+
+// What a search did, returned by every searcher alongside its result, whether it found the goal or not.
+// Searchers which run several searches (iterative deepening, IDA*, anytime, beam widening etc.)
+// add up the counts over all of them.
+// Displays as a table, with a row for each depth.
+#[derive(Clone, Debug, Default)]
+pub struct SearchStats {
+    pub expanded_nodes: u32,
+    // Children made by a valid move, including those pruned as duplicates.
+    pub generated_nodes: u32,
+    // Nodes dropped because their world state had already been reached or expanded as cheaply.
+    pub duplicates_pruned: u32,
+    // Nodes dropped because a block could no longer reach its goal, see DeadlockDetector.
+    pub deadlocks_pruned: u32,
+    pub max_fringe_size: usize,
+    // The most memory the fringe and explored states took up at once, in bytes.
+    // Expanded nodes which are only kept alive as parents aren't counted, so this is a lower estimate.
+    pub peak_memory: usize,
+    pub elapsed: Duration,
+    // The depth of the goal node, if it was found.
+    pub solution_depth: Option<u32>,
+    // How many times longer than optimal the plan found can be (1 if it's optimal), given an
+    // admissible heuristic. None if no plan was found, or the searcher gives no such bound.
+    pub suboptimality_bound: Option<f64>,
+    // The nodes generated and expanded at each depth, indexed by depth.
+    pub depths: Vec<DepthStats>,
+}
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DepthStats {
+    pub generated_nodes: u32,
+    pub expanded_nodes: u32,
+}
+impl SearchStats {
+    // The branching factor b a uniform tree as deep as the solution would need to hold as many
+    // nodes as were generated: generated = 1 + b + b^2 + ... + b^depth.
+    // None until a goal deeper than the start has been found.
+    pub fn effective_branching_factor(&self) -> Option<f64> {
+        let depth = self.solution_depth.filter(|&depth| depth > 0)?;
+        let generated_nodes = self.generated_nodes.max(depth + 1) as f64;
+        let tree_size = |branching_factor: f64| {
+            (0..depth + 1).map(|d| branching_factor.powi(d as i32)).sum::<f64>()
+        };
+        // The tree size only grows with b, so bisect between a chain (b = 1) and a star (b = generated).
+        let (mut low, mut high) = (1.0, generated_nodes);
+        for _ in 0..100 {
+            let middle = (low + high) / 2.0;
+            if tree_size(middle) < generated_nodes {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        Some((low + high) / 2.0)
+    }
+
+    pub(super) fn generated(&mut self, depth: u32) {
+        self.generated_nodes += 1;
+        self.at_depth(depth).generated_nodes += 1;
+    }
+    pub(super) fn expanded(&mut self, depth: u32) {
+        self.expanded_nodes += 1;
+        self.at_depth(depth).expanded_nodes += 1;
+    }
+    // Called with the size of the fringe, and the memory it and the explored states take up.
+    pub(super) fn fringe(&mut self, fringe_size: usize, memory: usize) {
+        self.max_fringe_size = self.max_fringe_size.max(fringe_size);
+        self.peak_memory = self.peak_memory.max(memory);
+    }
+    // Adds the counts of another search run as part of this one.
+    // The elapsed time, solution depth and suboptimality bound are left for the caller to set.
+    pub(super) fn add(&mut self, other: &SearchStats) {
+        self.expanded_nodes += other.expanded_nodes;
+        self.generated_nodes += other.generated_nodes;
+        self.duplicates_pruned += other.duplicates_pruned;
+        self.deadlocks_pruned += other.deadlocks_pruned;
+        self.max_fringe_size = self.max_fringe_size.max(other.max_fringe_size);
+        self.peak_memory = self.peak_memory.max(other.peak_memory);
+        for (depth, depth_stats) in other.depths.iter().enumerate() {
+            let totals = self.at_depth(depth as u32);
+            totals.generated_nodes += depth_stats.generated_nodes;
+            totals.expanded_nodes += depth_stats.expanded_nodes;
+        }
+    }
+
+    fn at_depth(&mut self, depth: u32) -> &mut DepthStats {
+        let depth = depth as usize;
+        if self.depths.len() <= depth {
+            self.depths.resize(depth + 1, DepthStats::default());
+        }
+        &mut self.depths[depth]
+    }
+}
+impl fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<28}{}", "Expanded Nodes:", self.expanded_nodes)?;
+        writeln!(f, "{:<28}{}", "Generated Nodes:", self.generated_nodes)?;
+        writeln!(f, "{:<28}{}", "Duplicates Pruned:", self.duplicates_pruned)?;
+        writeln!(f, "{:<28}{}", "Deadlocks Pruned:", self.deadlocks_pruned)?;
+        writeln!(f, "{:<28}{}", "Max Fringe Size:", self.max_fringe_size)?;
+        writeln!(f, "{:<28}{:.1} KiB", "Peak Memory (estimate):", self.peak_memory as f64 / 1024.0)?;
+        match self.effective_branching_factor() {
+            Some(branching_factor) => {
+                writeln!(f, "{:<28}{:.3}", "Effective Branching Factor:", branching_factor)?
+            }
+            None => writeln!(f, "{:<28}-", "Effective Branching Factor:")?,
+        }
+        match self.suboptimality_bound {
+            Some(bound) => writeln!(f, "{:<28}{}", "Suboptimality Bound:", bound)?,
+            None => writeln!(f, "{:<28}-", "Suboptimality Bound:")?,
+        }
+        writeln!(f, "{:<28}{:?}", "Elapsed Time:", self.elapsed)?;
+        writeln!(f, "{:<8}{:>12}{:>12}", "Depth", "Generated", "Expanded")?;
+        for (depth, depth_stats) in self.depths.iter().enumerate() {
+            writeln!(f,
+                     "{:<8}{:>12}{:>12}",
+                     depth,
+                     depth_stats.generated_nodes,
+                     depth_stats.expanded_nodes)?;
+        }
+        Ok(())
+    }
+}
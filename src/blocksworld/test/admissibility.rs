@@ -0,0 +1,120 @@
+use std::collections::{HashMap, VecDeque};
+
+use blocksworld::search::Heuristic;
+use blocksworld::world::{Direction, World};
+
+// A world a heuristic estimated as further from the goal than it really is.
+#[derive(Debug)]
+pub struct Overestimate {
+    pub world: World,
+    pub estimate: usize,
+    pub distance: usize,
+}
+
+// Checks a heuristic never overestimates, by comparing it against the exact distance to the goal
+// of every world which can reach it. Returns how many worlds were checked.
+// The search covers the whole state space, so this is only practical on small worlds.
+pub fn check_admissible<H: Heuristic + ?Sized>(goal_world: &World,
+                                               heuristic: &H)
+                                               -> Result<usize, Overestimate> {
+    let mut distances = HashMap::new();
+    let mut fringe = VecDeque::new();
+    for world in goal_world.agent_placements() {
+        distances.insert(world.state().clone(), 0);
+        fringe.push_back(world);
+    }
+    while let Some(world) = fringe.pop_front() {
+        let distance = distances[world.state()];
+        let estimate = heuristic.estimate(&world, goal_world);
+        if estimate > distance {
+            return Err(Overestimate {
+                world,
+                estimate,
+                distance,
+            });
+        }
+        for direction in Direction::directions_array().iter() {
+            for previous_world in world.previous_worlds(direction) {
+                if !distances.contains_key(previous_world.state()) {
+                    distances.insert(previous_world.state().clone(), distance + 1);
+                    fringe.push_back(previous_world);
+                }
+            }
+        }
+    }
+    Ok(distances.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_admissible;
+    use blocksworld::puzzle::Puzzle;
+    use blocksworld::search::{AgentHeuristic, Heuristic, ManhattanHeuristic, MaxHeuristic,
+                              PatternDatabase};
+    use blocksworld::world::{MoveRules, World};
+
+    // Small enough to check the whole state space quickly, with a wall for blocks to go around.
+    const PUZZLE: &str = "\
+* * * * * *
+*       @ *
+*   *     *
+* A B C   *
+* * * * * *
+
+* * * * * *
+*   A     *
+*   *   @ *
+*   B C   *
+* * * * * *
+";
+
+    // The goal world under each set of move rules.
+    fn goal_worlds() -> Vec<World> {
+        let puzzle = Puzzle::parse(PUZZLE).unwrap();
+        [MoveRules::Swap, MoveRules::Push, MoveRules::PushPull]
+            .iter()
+            .map(|&move_rules| puzzle.clone().with_move_rules(move_rules).goal)
+            .collect()
+    }
+
+    fn assert_admissible<H: Heuristic>(goal_world: &World, heuristic: &H) {
+        if let Err(overestimate) = check_admissible(goal_world, heuristic) {
+            panic!("overestimate under {} rules: {:?}",
+                   goal_world.move_rules().name(),
+                   overestimate);
+        }
+    }
+
+    #[test]
+    fn manhattan_is_admissible() {
+        for goal_world in goal_worlds() {
+            assert_admissible(&goal_world, &ManhattanHeuristic);
+        }
+    }
+
+    #[test]
+    fn agent_is_admissible() {
+        for goal_world in goal_worlds() {
+            assert_admissible(&goal_world, &AgentHeuristic);
+        }
+    }
+
+    #[test]
+    fn max_is_admissible() {
+        for goal_world in goal_worlds() {
+            let pattern_database = PatternDatabase::build(&goal_world, &['A', 'B']).unwrap();
+            let heuristic = MaxHeuristic::new().with(AgentHeuristic).with(pattern_database);
+            assert_admissible(&goal_world, &heuristic);
+        }
+    }
+
+    #[test]
+    fn pattern_database_is_admissible() {
+        for goal_world in goal_worlds() {
+            for block_chars in [['A', 'B'], ['B', 'C'], ['A', 'C']].iter() {
+                let pattern_database = PatternDatabase::build(&goal_world, block_chars).unwrap();
+                assert_admissible(&goal_world, &pattern_database);
+            }
+        }
+    }
+}
@@ -4,27 +4,44 @@ use blocksworld::world::Location;
 
 use bidir_map::BidirMap;
 
-use blocksworld::search::Searcher;
-use blocksworld::search::Node;
+use blocksworld::search::SearchOptions;
 use blocksworld::search::AStarSearcher;
 use blocksworld::search::BreadthFirstSearcher;
 use blocksworld::search::DepthFirstSearcher;
 use blocksworld::search::IterativeDeepeningSearcher;
+use blocksworld::search::BidirectionalSearcher;
 
-mod problem_generators;
+pub mod problem_generators;
+mod admissibility;
+mod state_space;
+pub use self::admissibility::{check_admissible, Overestimate};
+pub use self::state_space::StateSpace;
 
-pub fn test() {
+// Runs every searcher over generated problems of increasing difficulty, towards the README's goal.
+pub fn test(options: &SearchOptions) {
     let mut entity_goal_positions = BidirMap::new();
     entity_goal_positions.insert(Entity::Block('A'), Location::new(1, 1));
     entity_goal_positions.insert(Entity::Block('B'), Location::new(1, 2));
     entity_goal_positions.insert(Entity::Block('C'), Location::new(1, 3));
     entity_goal_positions.insert(Entity::Agent, Location::new(3, 3));
     let goal_world = World::new(4, 4, &entity_goal_positions).unwrap();
-    let solutions = problem_generators::solution_depth_difficulty(goal_world.clone());
-    for &(ref difficulty, ref world) in solutions.iter() {
+    test_with_goal(&goal_world, options);
+}
+
+// Runs every searcher over problems of increasing difficulty generated from a goal world,
+// which can have walls.
+// Under graph search repeated states are pruned, so BFS and IDS are no longer skipped on deep problems.
+// The seed is printed first; running again with it set in the options repeats every problem and result.
+pub fn test_with_goal(goal_world: &World, options: &SearchOptions) {
+    let mut options = options.clone();
+    let seed = options.fix_seed();
+    println!("Seed:\t{}", seed);
+
+    let solutions = problem_generators::solution_depth_difficulty(goal_world.clone(), seed);
+    for (difficulty, world) in solutions.iter() {
         world.pretty_print();
         println!("Difficulty:\t{}", difficulty);
-        let averages = run_all_searchers_average(world, &goal_world, *difficulty);
+        let averages = run_all_searchers_average(world, goal_world, *difficulty, &options);
         println!("A* Search Average Expanded Nodes:\t{}", averages[0]);
         println!("Depth First Search Average Expanded Nodes:\t{}",
                  averages[1]);
@@ -32,37 +49,54 @@ pub fn test() {
                  averages[2]);
         println!("Iterative Deepening Search Average Expanded Nodes:\t{}",
                  averages[3]);
+        println!("Bidirectional Breadth First Search Average Expanded Nodes:\t{}",
+                 averages[4]);
 
     }
 }
 
-fn run_all_searchers_average(start_world: &World, goal_world: &World, difficulty: u8) -> Vec<u32> {
+fn run_all_searchers_average(start_world: &World,
+                             goal_world: &World,
+                             difficulty: u8,
+                             options: &SearchOptions)
+                             -> Vec<u32> {
     let try_runs = 100;
-    let mut runs = vec![0; 4];
+    let mut runs = [0; 5];
 
-    let mut totals = vec![0; 4];
+    let mut totals = vec![0; 5];
 
     for run in 0..(try_runs + 1) {
+        // Every run gets its own seed, derived from the benchmark's, so averages are over different orders.
+        let mut run_options = options.clone();
+        run_options.seed = options.seed.map(|seed| seed.wrapping_add(run as usize));
+        let mut a_star_searcher = AStarSearcher::new(start_world.clone(), goal_world.clone())
+            .with_options(run_options.clone());
+        let mut depth_searcher = DepthFirstSearcher::new(start_world.clone(), goal_world.clone())
+            .with_options(run_options.clone());
+        let mut breadth_searcher = BreadthFirstSearcher::new(start_world.clone(),
+                                                             goal_world.clone())
+            .with_options(run_options.clone());
+        let mut iterative_deepening_searcher =
+            IterativeDeepeningSearcher::new(start_world.clone(), goal_world.clone())
+                .with_options(run_options.clone());
+        let mut bidirectional_searcher = BidirectionalSearcher::new(start_world.clone(),
+                                                                    goal_world.clone())
+            .with_options(run_options);
+
         runs[0] = run;
-        totals[0] +=
-            AStarSearcher::new(start_world.clone(), goal_world.clone()).search().unwrap().1;
+        totals[0] += a_star_searcher.search().unwrap().1.expanded_nodes;
         runs[1] = run;
-        totals[1] +=
-            DepthFirstSearcher::new(start_world.clone(), goal_world.clone()).search().unwrap().1;
-        if difficulty <= 14 {
+        totals[1] += depth_searcher.search().unwrap().1.expanded_nodes;
+        // Tree search blows up exponentially beyond this difficulty.
+        if difficulty <= 14 || options.graph_search {
             runs[2] = run;
-            totals[2] += BreadthFirstSearcher::new(start_world.clone(), goal_world.clone())
-                .search()
-                .unwrap()
-                .1;
-        }
-        if difficulty <= 14 {
+            totals[2] += breadth_searcher.search().unwrap().1.expanded_nodes;
             runs[3] = run;
-            totals[3] += IterativeDeepeningSearcher::new(start_world.clone(), goal_world.clone())
-                .search()
-                .unwrap()
-                .1;
+            totals[3] += iterative_deepening_searcher.search().unwrap().1.expanded_nodes;
         }
+        // Each half only searches about half as deep, so this is never skipped.
+        runs[4] = run;
+        totals[4] += bidirectional_searcher.search().unwrap().1.expanded_nodes;
 
     }
 
@@ -75,3 +109,50 @@ fn run_all_searchers_average(start_world: &World, goal_world: &World, difficulty
 
     totals
 }
+
+// Puzzles the searchers' unit tests solve, with the length of their optimal plans as the reference.
+#[cfg(test)]
+pub mod fixtures {
+    use blocksworld::puzzle::Puzzle;
+    use blocksworld::search::{BreadthFirstSearcher, Node};
+    use blocksworld::world::MoveRules;
+
+    pub struct TestPuzzle {
+        // The puzzle's file name in puzzles/, without the extension.
+        pub name: &'static str,
+        pub puzzle: Puzzle,
+        // Found by breadth first graph search.
+        pub optimal_length: u32,
+    }
+
+    // Puzzles from puzzles/ under each set of rules they can be solved by, small enough for a
+    // debug build. The others start deadlocked under push rules.
+    const PUZZLES: [(&str, &str, MoveRules); 7] =
+        [("readme", include_str!("../../../puzzles/readme.txt"), MoveRules::Swap),
+         ("readme", include_str!("../../../puzzles/readme.txt"), MoveRules::PushPull),
+         ("rooms_6x4", include_str!("../../../puzzles/rooms_6x4.txt"), MoveRules::Swap),
+         ("warehouse_6x5", include_str!("../../../puzzles/warehouse_6x5.txt"), MoveRules::Swap),
+         ("warehouse_6x5", include_str!("../../../puzzles/warehouse_6x5.txt"), MoveRules::Push),
+         ("warehouse_6x5",
+          include_str!("../../../puzzles/warehouse_6x5.txt"),
+          MoveRules::PushPull),
+         ("tower_5x5", include_str!("../../../puzzles/tower_5x5.txt"), MoveRules::Swap)];
+
+    pub fn test_puzzles() -> Vec<TestPuzzle> {
+        PUZZLES.iter()
+            .map(|&(name, text, move_rules)| {
+                let puzzle = Puzzle::parse(text).unwrap().with_move_rules(move_rules);
+                let (goal_node, _) = BreadthFirstSearcher::new(puzzle.start.clone(),
+                                                               puzzle.goal.clone())
+                    .with_graph_search()
+                    .search()
+                    .unwrap();
+                TestPuzzle {
+                    name,
+                    puzzle,
+                    optimal_length: goal_node.get_depth(),
+                }
+            })
+            .collect()
+    }
+}
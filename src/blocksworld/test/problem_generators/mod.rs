@@ -1,5 +1,6 @@
 mod solution_depth_difficulty;
+#[allow(dead_code)] // Not implemented yet.
 mod grid_size_difficulty;
 mod block_amount_difficulty;
 
-pub use self::solution_depth_difficulty::solution_depth_difficulty;
\ No newline at end of file
+pub use self::solution_depth_difficulty::{solution_depth_difficulty, SolutionDepthSearcher};
\ No newline at end of file
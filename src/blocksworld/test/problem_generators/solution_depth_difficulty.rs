@@ -2,34 +2,52 @@ use blocksworld::world::World;
 use blocksworld::search::BasicNode;
 use blocksworld::search::Node;
 use blocksworld::search::Searcher;
+use blocksworld::search::{AStarSearcher, DirectionOrder, SearchOptions};
 
 use std::collections::BTreeMap;
+use std::collections::btree_map::Entry;
 use std::rc::Rc;
 
-pub fn solution_depth_difficulty(goal_world: World) -> Vec<(u8, World)> {
-    let searcher = SolutionDepthSearcher::new(goal_world, 26);
+// The same seed always generates the same problems.
+pub fn solution_depth_difficulty(goal_world: World, seed: usize) -> Vec<(u8, World)> {
+    let searcher = SolutionDepthSearcher::new(goal_world, 26).with_seed(seed);
     searcher.search()
 }
 
 // Uses a depth first style search to find problems of difficulties up to a certain difficulty.
 // Each node generated has an A* search run on it to find its optimal path  size to the goal.
+// The walk gives up after MAX_WALK_LENGTH moves, as walls can leave a world with no problems that
+// difficult, returning the difficulties it found.
+// Worlds which can't reach the goal, as pushes can leave them, are skipped.
 pub struct SolutionDepthSearcher {
     start_world: World,
+    options: SearchOptions,
     fringe: Option<BasicNode>,
     max_difficulty: u8,
     solutions: BTreeMap<u8, World>,
 }
 impl SolutionDepthSearcher {
+    pub const MAX_WALK_LENGTH: u32 = 2000;
+
     pub fn new(start_world: World, max_difficulty: u8) -> SolutionDepthSearcher {
         SolutionDepthSearcher {
-            start_world: start_world,
+            start_world,
+            options: SearchOptions {
+                max_depth: Some(SolutionDepthSearcher::MAX_WALK_LENGTH),
+                ..SearchOptions::default()
+            },
             fringe: None,
-            max_difficulty: max_difficulty,
+            max_difficulty,
             solutions: BTreeMap::new(),
         }
     }
+    // Seeds the walk away from the goal world.
+    pub fn with_seed(mut self, seed: usize) -> SolutionDepthSearcher {
+        self.options.seed = Some(seed);
+        self
+    }
     pub fn search(mut self) -> Vec<(u8, World)> {
-        let _ = Searcher::search(&mut self, None);
+        let _ = Searcher::search(&mut self);
         self.solutions.into_iter().collect::<Vec<(u8, World)>>()
     }
 }
@@ -41,6 +59,9 @@ impl Searcher for SolutionDepthSearcher {
     fn get_goal_world(&self) -> &World {
         &self.start_world
     }
+    fn get_options(&self) -> &SearchOptions {
+        &self.options
+    }
     fn goal_reached(&self, _: &Self::NodeType) -> bool {
         self.solutions.len() > self.max_difficulty as usize
     }
@@ -48,25 +69,35 @@ impl Searcher for SolutionDepthSearcher {
         self.fringe = Some(node);
     }
     fn fringe_pop(&mut self) -> Option<Self::NodeType> {
-        let node = match self.fringe.take() {
-            Some(node) => node,
-            None => return None,
+        let node = self.fringe.take()?;
+        // The order A* generates children in can't change the depth it finds, so don't shuffle them.
+        let mut a_star_searcher = AStarSearcher::new(node.get_world().clone(),
+                                                     self.get_goal_world().clone())
+            .with_options(SearchOptions {
+                direction_order: DirectionOrder::Fixed,
+                ..SearchOptions::default()
+            });
+        let result = match a_star_searcher.search() {
+            Ok(result) => result,
+            Err(_) => return Some(node),
         };
-        let mut a_star_searcher =
-            ::blocksworld::search::AStarSearcher::new(node.get_world().clone(),
-                                                      self.get_goal_world().clone());
-        let result = a_star_searcher.search().unwrap();
         // If we haven't already found a problem world at this depth, add it, to the Map
-        if !self.solutions.contains_key(&(result.0.get_depth() as u8)) {
-            self.solutions.insert(result.0.get_depth() as u8, node.get_world().clone());
+        if let Entry::Vacant(entry) = self.solutions.entry(result.0.get_depth() as u8) {
+            entry.insert(node.get_world().clone());
             println!("New solution, depth {}", result.0.get_depth());
         }
 
         Some(node)
     }
+    fn fringe_len(&self) -> usize {
+        self.fringe.iter().count()
+    }
+    fn fringe_clear(&mut self) {
+        self.fringe = None;
+    }
     fn new_node(&self,
                 depth: u32,
-                world: Box<World>,
+                world: World,
                 parent: Option<Rc<Self::NodeType>>)
                 -> Self::NodeType {
         Self::NodeType::new(depth, world, parent)
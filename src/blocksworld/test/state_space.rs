@@ -0,0 +1,325 @@
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
+
+use blocksworld::search::Heuristic;
+use blocksworld::world::{Direction, World, WorldState};
+
+// Every world reachable from a start world, with each one's exact distance to a goal: the ground
+// truth heuristics and generated problem difficulties can be checked against.
+// Holds the whole state space in memory, so this is only practical on small worlds.
+pub struct StateSpace {
+    start_world: World,
+    // In the order a breadth first search from the start world reached them.
+    states: Vec<WorldState>,
+    indices: HashMap<WorldState, u32>,
+    // The worlds each world's moves lead to, by index.
+    successors: Vec<Vec<u32>>,
+    // The fewest moves from each world to the goal, or None if it can't reach it.
+    goal_distances: Vec<Option<u32>>,
+}
+impl StateSpace {
+    // The most worlds diameter works out every eccentricity of, taking a search from each.
+    pub const MAX_DIRECTED_DIAMETER_WORLDS: usize = 10000;
+
+    // Enumerates every world the agent can move the start world into, then searches back from
+    // every goal state to find how far each is from the goal.
+    pub fn enumerate(start_world: &World, goal_world: &World) -> StateSpace {
+        let mut states = vec![start_world.state().clone()];
+        let mut indices = HashMap::new();
+        indices.insert(start_world.state().clone(), 0);
+        let mut successors = Vec::new();
+        let mut index = 0;
+        while index < states.len() {
+            let world = start_world.with_state(states[index].clone());
+            let mut world_successors = Vec::new();
+            for direction in Direction::directions_array().iter() {
+                if let Ok(next_world) = world.clone_and_move_agent(direction) {
+                    let next_index = match indices.get(next_world.state()) {
+                        Some(&next_index) => next_index,
+                        None => {
+                            let next_index = states.len() as u32;
+                            indices.insert(next_world.state().clone(), next_index);
+                            states.push(next_world.state().clone());
+                            next_index
+                        }
+                    };
+                    world_successors.push(next_index);
+                }
+            }
+            successors.push(world_successors);
+            index += 1;
+        }
+
+        // Every world on the way to the goal from a reachable world is reachable too,
+        // so the search back from the goal can stay within the reachable worlds.
+        let mut goal_distances = vec![None; states.len()];
+        let mut fringe = VecDeque::new();
+        for world in goal_world.agent_placements() {
+            if let Some(&index) = indices.get(world.state()) {
+                goal_distances[index as usize] = Some(0);
+                fringe.push_back(world);
+            }
+        }
+        while let Some(world) = fringe.pop_front() {
+            let distance = goal_distances[indices[world.state()] as usize].unwrap();
+            for direction in Direction::directions_array().iter() {
+                for previous_world in world.previous_worlds(direction) {
+                    if let Some(&index) = indices.get(previous_world.state()) {
+                        if goal_distances[index as usize].is_none() {
+                            goal_distances[index as usize] = Some(distance + 1);
+                            fringe.push_back(previous_world);
+                        }
+                    }
+                }
+            }
+        }
+
+        StateSpace {
+            start_world: start_world.clone(),
+            states,
+            indices,
+            successors,
+            goal_distances,
+        }
+    }
+    // The number of worlds reachable from the start world, including it.
+    pub fn size(&self) -> usize {
+        self.states.len()
+    }
+    // The number of moves between reachable worlds.
+    pub fn move_count(&self) -> usize {
+        self.successors.iter().map(|successors| successors.len()).sum()
+    }
+    // The fewest moves from the world to the goal, or None if it can't reach the goal,
+    // or isn't reachable from the start world.
+    pub fn goal_distance(&self, world: &World) -> Option<u32> {
+        self.goal_distances[*self.indices.get(world.state())? as usize]
+    }
+    // How many worlds are each number of moves from the goal, indexed by distance.
+    pub fn distance_distribution(&self) -> Vec<usize> {
+        let mut distribution = Vec::new();
+        for &distance in self.goal_distances.iter().flatten() {
+            let distance = distance as usize;
+            if distribution.len() <= distance {
+                distribution.resize(distance + 1, 0);
+            }
+            distribution[distance] += 1;
+        }
+        distribution
+    }
+    // The number of reachable worlds which can't reach the goal.
+    pub fn unsolvable_count(&self) -> usize {
+        self.goal_distances.iter().filter(|distance| distance.is_none()).count()
+    }
+    // The heuristic's mean estimate for the worlds at each distance from the goal, indexed by
+    // distance. A mean above its distance means the heuristic overestimates somewhere.
+    pub fn mean_estimates<H: Heuristic + ?Sized>(&self,
+                                                 heuristic: &H,
+                                                 goal_world: &World)
+                                                 -> Vec<f64> {
+        let distribution = self.distance_distribution();
+        let mut totals = vec![0.0; distribution.len()];
+        for (state, distance) in self.states.iter().zip(&self.goal_distances) {
+            if let Some(distance) = *distance {
+                let world = self.start_world.with_state(state.clone());
+                totals[distance as usize] += heuristic.estimate(&world, goal_world) as f64;
+            }
+        }
+        totals.iter().zip(distribution).map(|(total, count)| total / count as f64).collect()
+    }
+    // The worlds furthest from the goal which can still reach it, up to limit of them.
+    pub fn hardest_starts(&self, limit: usize) -> Vec<(u32, World)> {
+        let mut hardest = self.states
+            .iter()
+            .zip(&self.goal_distances)
+            .filter_map(|(state, distance)| distance.map(|distance| (distance, state)))
+            .collect::<Vec<(u32, &WorldState)>>();
+        // Stable, so worlds equally far keep the order they were reached in.
+        hardest.sort_by_key(|&(distance, _)| cmp::Reverse(distance));
+        hardest.into_iter()
+            .take(limit)
+            .map(|(distance, state)| (distance, self.start_world.with_state(state.clone())))
+            .collect()
+    }
+    // The most moves the shortest path between any two reachable worlds takes.
+    // When every move can be undone, as swaps can, the eccentricities of a few worlds are usually
+    // enough to pin it down (the iFUB algorithm). Otherwise every world's is needed, so this gives
+    // up with None beyond MAX_DIRECTED_DIAMETER_WORLDS worlds.
+    pub fn diameter(&self) -> Option<u32> {
+        if !self.is_undirected() {
+            if self.states.len() > StateSpace::MAX_DIRECTED_DIAMETER_WORLDS {
+                return None;
+            }
+            return (0..self.states.len()).map(|index| self.eccentricity(index).0).max();
+        }
+
+        // Start from the middle of a long path, found by two sweeps, so the fringes are small.
+        let (_, far) = self.eccentricity(0);
+        let (far_eccentricity, _) = self.eccentricity(far);
+        let middle = self.path_middle(far, far_eccentricity);
+        let distances = self.distances_from(middle);
+        let middle_eccentricity = distances.iter().cloned().max().unwrap();
+
+        let mut lower_bound = far_eccentricity.max(middle_eccentricity);
+        let mut level = middle_eccentricity;
+        // Worlds at distance level from the middle are at most 2 * level apart from each other and
+        // anything closer, so once one of them is that far out, nothing further in can beat it.
+        while level > 0 && lower_bound < 2 * level {
+            let level_eccentricity = distances.iter()
+                .enumerate()
+                .filter(|&(_, &distance)| distance == level)
+                .map(|(index, _)| self.eccentricity(index).0)
+                .max()
+                .unwrap_or(0);
+            lower_bound = lower_bound.max(level_eccentricity);
+            if lower_bound > 2 * (level - 1) {
+                break;
+            }
+            level -= 1;
+        }
+        Some(lower_bound)
+    }
+
+    fn is_undirected(&self) -> bool {
+        self.successors.iter().enumerate().all(|(index, successors)| {
+            successors.iter()
+                .all(|&successor| self.successors[successor as usize].contains(&(index as u32)))
+        })
+    }
+
+    // The distance from the world at index to every world it can reach, u32::MAX for the rest.
+    fn distances_from(&self, index: usize) -> Vec<u32> {
+        let mut distances = vec![u32::MAX; self.states.len()];
+        distances[index] = 0;
+        let mut fringe = VecDeque::new();
+        fringe.push_back(index);
+        while let Some(index) = fringe.pop_front() {
+            for &successor in &self.successors[index] {
+                if distances[successor as usize] == u32::MAX {
+                    distances[successor as usize] = distances[index] + 1;
+                    fringe.push_back(successor as usize);
+                }
+            }
+        }
+        distances
+    }
+
+    // The distance to the furthest world the world at index can reach, and that world's index.
+    fn eccentricity(&self, index: usize) -> (u32, usize) {
+        self.distances_from(index)
+            .iter()
+            .enumerate()
+            .filter(|&(_, &distance)| distance != u32::MAX)
+            .map(|(index, &distance)| (distance, index))
+            .max()
+            .unwrap()
+    }
+
+    // The world halfway along a shortest path of the given length from the world at index.
+    fn path_middle(&self, index: usize, length: u32) -> usize {
+        let distances = self.distances_from(index);
+        let mut middle = distances.iter().position(|&distance| distance == length).unwrap();
+        for _ in 0..length / 2 {
+            middle = self.successors[middle]
+                .iter()
+                .map(|&successor| successor as usize)
+                .find(|&successor| distances[successor] + 1 == distances[middle])
+                .unwrap();
+        }
+        middle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StateSpace;
+    use blocksworld::puzzle::Puzzle;
+    use blocksworld::world::{Entity, Location, MoveRules, World};
+
+    // One block in a 2x2 world, to go to the bottom right. Under swap rules each world has two
+    // moves, both of which can be undone, so the 12 worlds form a single cycle.
+    const PUZZLE: &str = "\
+* * * *
+* @ A *
+*     *
+* * * *
+
+* * * *
+* @   *
+*   A *
+* * * *
+";
+
+    fn locations(world: &World) -> (Location, Location) {
+        (world.get_entity_location(&Entity::Agent).unwrap(),
+         world.get_entity_location(&Entity::Block('A')).unwrap())
+    }
+
+    #[test]
+    fn measures_a_cycle_of_worlds() {
+        let puzzle = Puzzle::parse(PUZZLE).unwrap();
+        let state_space = StateSpace::enumerate(&puzzle.start, &puzzle.goal);
+        assert_eq!(state_space.size(), 12);
+        assert_eq!(state_space.move_count(), 24);
+        assert_eq!(state_space.unsolvable_count(), 0);
+        // Three worlds with the block on its goal, then two either side of them at each distance
+        // round the cycle, meeting at the world with the block in the opposite corner.
+        assert_eq!(state_space.distance_distribution(), vec![3, 2, 2, 2, 2, 1]);
+        assert_eq!(state_space.diameter(), Some(6));
+        assert_eq!(state_space.goal_distance(&puzzle.start), Some(3));
+
+        let hardest = state_space.hardest_starts(3);
+        assert_eq!(hardest.len(), 3);
+        assert_eq!(hardest[0].0, 5);
+        assert_eq!(locations(&hardest[0].1), (Location::new(1, 1), Location::new(0, 0)));
+        // The agent is next to the block in the opposite corner, on either side of it.
+        let mut next_hardest = hardest[1..]
+            .iter()
+            .map(|&(distance, ref world)| (distance, locations(world)))
+            .collect::<Vec<_>>();
+        next_hardest.sort_by_key(|&(_, (ref agent, _))| (agent.x(), agent.y()));
+        assert_eq!(next_hardest,
+                   vec![(4, (Location::new(0, 1), Location::new(0, 0))),
+                        (4, (Location::new(1, 0), Location::new(0, 0)))]);
+        assert_eq!(state_space.hardest_starts(20).len(), 12);
+    }
+
+    #[test]
+    fn measures_worlds_which_cant_reach_the_goal() {
+        // Under push rules a block in a 2x2 world can never move, as there's no room behind it.
+        let puzzle = Puzzle::parse(PUZZLE).unwrap().with_move_rules(MoveRules::Push);
+        let state_space = StateSpace::enumerate(&puzzle.start, &puzzle.goal);
+        assert_eq!(state_space.size(), 3);
+        assert_eq!(state_space.unsolvable_count(), 3);
+        assert!(state_space.distance_distribution().is_empty());
+        assert!(state_space.hardest_starts(3).is_empty());
+        assert_eq!(state_space.goal_distance(&puzzle.start), None);
+        // The agent walks between the other three cells, around the block.
+        assert_eq!(state_space.diameter(), Some(2));
+    }
+
+    #[test]
+    fn measures_moves_which_cant_be_undone() {
+        // The agent can push the block to the end of the row, but can't bring it back.
+        let puzzle = Puzzle::parse("\
+* * * * *
+* @ A   *
+* * * * *
+
+* * * * *
+* @   A *
+* * * * *
+")
+            .unwrap()
+            .with_move_rules(MoveRules::Push);
+        let state_space = StateSpace::enumerate(&puzzle.start, &puzzle.goal);
+        assert_eq!(state_space.size(), 3);
+        assert_eq!(state_space.move_count(), 3);
+        assert_eq!(state_space.distance_distribution(), vec![2, 1]);
+        // The start world reaches the agent walking back from the pushed block in two moves.
+        assert_eq!(state_space.diameter(), Some(2));
+        let hardest = state_space.hardest_starts(1);
+        assert_eq!(hardest[0].0, 1);
+        assert_eq!(locations(&hardest[0].1), locations(&puzzle.start));
+    }
+}
@@ -0,0 +1,170 @@
+use std::fmt;
+
+use blocksworld::plan::{Plan, PlanError};
+use blocksworld::search::{AStarSearcher, Node, SearchOptions};
+use blocksworld::world::{World, WorldError};
+
+// The outcome of replaying a move sequence from a start world.
+#[derive(Debug)]
+pub struct Validation {
+    pub plan: Plan,
+    // The step (counting from 1) whose move could not be made, and why.
+    pub failed_step: Option<(usize, WorldError)>,
+    pub reaches_goal: bool,
+    // Length of an optimal plan found by A*. Only searched for if the plan reaches the goal, and
+    // None if A* gave up within the limits it was given.
+    pub optimal_length: Option<usize>,
+}
+impl Validation {
+    pub fn is_optimal(&self) -> bool {
+        self.reaches_goal && self.optimal_length == Some(self.plan.len())
+    }
+}
+impl fmt::Display for Validation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Plan:\t{} ({} moves)", self.plan, self.plan.len())?;
+        if let Some((step, ref error)) = self.failed_step {
+            writeln!(f,
+                     "Step {} ({}) failed:\t{:?}",
+                     step,
+                     self.plan.moves()[step - 1].to_char(),
+                     error)?;
+        }
+        writeln!(f, "Reaches goal:\t{}", self.reaches_goal)?;
+        match self.optimal_length {
+            Some(optimal_length) => {
+                write!(f,
+                       "Optimal:\t{} (optimal plan has {} moves)",
+                       self.is_optimal(),
+                       optimal_length)
+            }
+            None if self.reaches_goal => {
+                write!(f, "Optimal:\tunknown (no optimal plan found within the search limits)")
+            }
+            None => write!(f, "Optimal:\tfalse"),
+        }
+    }
+}
+
+// Checks a move sequence such as "UULDR" from another tool or written by hand.
+// Replays it from the start world, stopping at the first move which can't be made,
+// and compares it against an A* solution if it reaches the goal world.
+// A* can take a long time on hard puzzles, so it searches within the options' node budget and
+// timeout.
+pub fn validate(start_world: &World,
+                goal_world: &World,
+                moves: &str,
+                options: &SearchOptions)
+                -> Result<Validation, PlanError> {
+    let plan = Plan::parse(moves)?;
+
+    let mut failed_step = None;
+    let mut final_world = start_world.clone();
+    for (step, result) in plan.replay(start_world).enumerate() {
+        match result {
+            Ok(world) => final_world = world,
+            Err(error) => {
+                failed_step = Some((step + 1, error));
+                break;
+            }
+        }
+    }
+    let reaches_goal = failed_step.is_none() && final_world.eq_ignore_agent(goal_world);
+
+    let optimal_length = if reaches_goal {
+        AStarSearcher::new(start_world.clone(), goal_world.clone())
+            .with_options(options.clone())
+            .search()
+            .ok()
+            .map(|(goal_node, _)| goal_node.get_depth() as usize)
+    } else {
+        None
+    };
+
+    Ok(Validation {
+        plan,
+        failed_step,
+        reaches_goal,
+        optimal_length,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate;
+    use blocksworld::puzzle::Puzzle;
+    use blocksworld::search::SearchOptions;
+    use blocksworld::world::WorldError;
+
+    // The agent steps left twice, swapping with A on the second move.
+    fn puzzle() -> Puzzle {
+        Puzzle::parse("\
+* * * * *
+* A   @ *
+* * * * *
+
+* * * * *
+*   A @ *
+* * * * *
+")
+            .unwrap()
+    }
+
+    #[test]
+    fn accepts_optimal_plan() {
+        let puzzle = puzzle();
+        let options = SearchOptions::default();
+        let validation = validate(&puzzle.start, &puzzle.goal, "LL", &options).unwrap();
+        assert!(validation.failed_step.is_none());
+        assert!(validation.reaches_goal);
+        assert_eq!(validation.optimal_length, Some(2));
+        assert!(validation.is_optimal());
+    }
+
+    #[test]
+    fn accepts_longer_plan() {
+        let puzzle = puzzle();
+        let options = SearchOptions::default();
+        let validation = validate(&puzzle.start, &puzzle.goal, "LRLL", &options).unwrap();
+        assert!(validation.failed_step.is_none());
+        assert!(validation.reaches_goal);
+        assert_eq!(validation.optimal_length, Some(2));
+        assert!(!validation.is_optimal());
+    }
+
+    #[test]
+    fn reports_failed_step() {
+        let puzzle = puzzle();
+        let options = SearchOptions::default();
+        let validation = validate(&puzzle.start, &puzzle.goal, "LULL", &options).unwrap();
+        match validation.failed_step {
+            Some((2, WorldError::EntityOutOfBoundsError)) => {}
+            failed_step => panic!("unexpected failed step: {:?}", failed_step),
+        }
+        assert!(!validation.reaches_goal);
+        assert_eq!(validation.optimal_length, None);
+    }
+
+    #[test]
+    fn reports_plan_ending_off_goal() {
+        let puzzle = puzzle();
+        let options = SearchOptions::default();
+        let validation = validate(&puzzle.start, &puzzle.goal, "L", &options).unwrap();
+        assert!(validation.failed_step.is_none());
+        assert!(!validation.reaches_goal);
+        assert_eq!(validation.optimal_length, None);
+        assert!(!validation.is_optimal());
+    }
+    #[test]
+    fn gives_up_on_optimal_length_within_limits() {
+        let puzzle = puzzle();
+        let options = SearchOptions {
+            node_budget: Some(1),
+            ..SearchOptions::default()
+        };
+        let validation = validate(&puzzle.start, &puzzle.goal, "LL", &options).unwrap();
+        assert!(validation.reaches_goal);
+        assert_eq!(validation.optimal_length, None);
+        assert!(!validation.is_optimal());
+    }
+}
@@ -1,53 +1,257 @@
 use bidir_map::BidirMap;
 
-use std::iter;
+use std::collections::VecDeque;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::rc::Rc;
+use std::str::FromStr;
 
-#[derive(Debug, Clone, PartialEq)]
+// A world is a shared, immutable layout plus the packed positions of its entities,
+// so cloning one only copies a handful of cell indices.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct World {
-    pub entities: BidirMap<Entity, Location>,
+    layout: Rc<Layout>,
+    state: WorldState,
+}
+
+// The parts of a world which no move can change.
+#[derive(Clone)]
+struct Layout {
     width: isize,
     height: isize,
+    blocks: Vec<char>, // Sorted, so a block's position in the state can be found by binary search.
+    walls: Vec<bool>, // Indexed by cell, empty if there are no walls.
+    // The fewest steps between each pair of cells avoiding walls, indexed by
+    // from * cell_count + to, or u16::MAX if there's no way between them. Only built for grids
+    // with walls up to MAX_DISTANCE_TABLE_CELLS cells, as it takes cell_count squared entries.
+    distances: Option<Vec<u16>>,
+    move_rules: MoveRules,
+}
+impl Layout {
+    fn is_wall(&self, cell: u16) -> bool {
+        self.walls.get(cell as usize).cloned().unwrap_or(false)
+    }
+}
+// The distance table is worked out from the walls, so isn't compared or printed.
+impl PartialEq for Layout {
+    fn eq(&self, other: &Layout) -> bool {
+        self.width == other.width && self.height == other.height && self.blocks == other.blocks &&
+        self.walls == other.walls && self.move_rules == other.move_rules
+    }
+}
+impl Eq for Layout {}
+impl fmt::Debug for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let walls = (0..self.walls.len()).filter(|&cell| self.walls[cell]).collect::<Vec<usize>>();
+        f.debug_struct("Layout")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("blocks", &self.blocks)
+            .field("walls", &walls)
+            .field("move_rules", &self.move_rules)
+            .finish()
+    }
+}
+
+// What happens to a block the agent moves into. Chosen per world with World::with_move_rules.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MoveRules {
+    // The block swaps into the agent's old cell, so every move can be undone.
+    #[default]
+    Swap,
+    // Sokoban style: the block is pushed one cell further on, which fails if a wall, another
+    // block or the edge of the grid is in the way. Pushes can't always be undone, so blocks can
+    // get stuck.
+    Push,
+    // Push, and the block behind the agent, if there is one, is pulled along after it.
+    PushPull,
+}
+impl MoveRules {
+    // The most blocks a single move can move, each by one cell.
+    pub fn blocks_per_move(self) -> usize {
+        match self {
+            MoveRules::Swap | MoveRules::Push => 1,
+            MoveRules::PushPull => 2,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            MoveRules::Swap => "swap",
+            MoveRules::Push => "push",
+            MoveRules::PushPull => "pushpull",
+        }
+    }
+}
+impl FromStr for MoveRules {
+    type Err = ();
+    fn from_str(name: &str) -> Result<MoveRules, ()> {
+        match name {
+            "swap" => Ok(MoveRules::Swap),
+            "push" => Ok(MoveRules::Push),
+            "pushpull" => Ok(MoveRules::PushPull),
+            _ => Err(()),
+        }
+    }
+}
+
+// Compact, hashable encoding of where every entity is.
+// Holds the cell index (y * width + x) of the agent followed by that of each block in layout order.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WorldState {
+    cells: Box<[u16]>,
+}
+impl WorldState {
+    // The bytes the cell indices take up on the heap, for estimating how much memory a search uses.
+    pub fn heap_size(&self) -> usize {
+        mem::size_of_val(&*self.cells)
+    }
 }
 
 impl World {
+    // The largest grid with walls walking_distance keeps a table of distances for (2 MiB of them).
+    pub const MAX_DISTANCE_TABLE_CELLS: usize = 1024;
+
+    // Places the agent and blocks. Walls are added afterwards with with_walls, so fails with
+    // UnplaceableEntityError for an Entity::Wall or Entity::None.
     pub fn new(width: usize,
                height: usize,
                entity_starts: &BidirMap<Entity, Location>)
                -> Result<World, WorldError> {
+        if width * height > u16::MAX as usize + 1 {
+            return Err(WorldError::GridTooLargeError);
+        }
         let width = width as isize;
         let height = height as isize;
         World::check_start_invariants(width, height, entity_starts)?;
 
+        let mut blocks = entity_starts.first_col()
+            .filter_map(|entity| match *entity {
+                Entity::Block(block_char) => Some(block_char),
+                _ => None,
+            })
+            .collect::<Vec<char>>();
+        blocks.sort();
+        let mut world = World {
+            state: WorldState { cells: vec![0; blocks.len() + 1].into_boxed_slice() },
+            layout: Rc::new(Layout {
+                width,
+                height,
+                blocks,
+                walls: Vec::new(),
+                distances: None,
+                move_rules: MoveRules::default(),
+            }),
+        };
+        for (entity, location) in entity_starts.iter() {
+            match *entity {
+                Entity::Agent | Entity::Block(_) => {
+                    world.set_entity_location(entity.clone(), location.clone())
+                }
+                Entity::Wall | Entity::None => unreachable!(),
+            }
+        }
+
+        Ok(world)
+    }
+    // This world with immovable walls in the given cells, which the agent can't move into.
+    // Fails if a wall is out of bounds or on the agent or a block.
+    pub fn with_walls(self, walls: &[Location]) -> Result<World, WorldError> {
+        let mut wall_cells = vec![false; self.cell_count()];
+        for location in walls {
+            Self::check_location_invariants(self.layout.width, self.layout.height, location)?;
+            let cell = self.location_cell(location);
+            if self.state.cells.contains(&cell) {
+                return Err(WorldError::WallCollisionError);
+            }
+            wall_cells[cell as usize] = true;
+        }
+        if !wall_cells.contains(&true) {
+            wall_cells.clear();
+        }
+        let distances = if !wall_cells.is_empty() &&
+                           self.cell_count() <= World::MAX_DISTANCE_TABLE_CELLS {
+            Some(self.distance_table(&wall_cells))
+        } else {
+            None
+        };
         Ok(World {
-            entities: entity_starts.clone(),
-            width: width,
-            height: height,
+            layout: Rc::new(Layout {
+                walls: wall_cells,
+                distances,
+                ..(*self.layout).clone()
+            }),
+            state: self.state,
         })
     }
+    // This world with the agent moving blocks by the given rules, rather than swapping with them.
+    pub fn with_move_rules(self, move_rules: MoveRules) -> World {
+        World {
+            layout: Rc::new(Layout { move_rules, ..(*self.layout).clone() }),
+            state: self.state,
+        }
+    }
+    pub fn move_rules(&self) -> MoveRules {
+        self.layout.move_rules
+    }
+    // Rebuilds a world with this world's layout from a packed state, e.g. one taken from an explored set.
+    pub fn with_state(&self, state: WorldState) -> World {
+        World {
+            layout: self.layout.clone(),
+            state,
+        }
+    }
+    pub fn state(&self) -> &WorldState {
+        &self.state
+    }
+    // Converts back to the map representation the world was built from.
+    pub fn entities(&self) -> BidirMap<Entity, Location> {
+        let mut entities = BidirMap::new();
+        entities.insert(Entity::Agent, self.cell_location(self.state.cells[0]));
+        for (block_char, location) in self.blocks() {
+            entities.insert(Entity::Block(block_char), location);
+        }
+        entities
+    }
+    pub fn blocks<'a>(&'a self) -> impl Iterator<Item = (char, Location)> + 'a {
+        self.layout
+            .blocks
+            .iter()
+            .zip(self.state.cells[1..].iter())
+            .map(move |(&block_char, &cell)| (block_char, self.cell_location(cell)))
+    }
+    pub fn width(&self) -> usize {
+        self.layout.width as usize
+    }
+    pub fn height(&self) -> usize {
+        self.layout.height as usize
+    }
     pub fn pretty_print(&self) {
-        let wall_char = '*';
-        let agent_char = '@';
-        let none_char = ' ';
-        let padding_char = ' ';
-
-        let horizontal_wall = iter::repeat(format!("{}{}", wall_char, padding_char))
-            .take(self.width as usize + 2)
-            .collect::<String>();
-
-        println!("{}", horizontal_wall);
-        for y in 0..self.height {
-            print!("{}{}", wall_char, padding_char);
-            for x in 0..self.width {
-                match self.get_grid_location(&Location::new(x, y)).unwrap() {
-                    Entity::Agent => print!("{}", agent_char),
-                    Entity::Block(block_char) => print!("{}", block_char),
-                    Entity::None => print!("{}", none_char),
-                }
-                print!("{}", padding_char);
+        print!("{}", self);
+    }
+    // Every wall's location, in cell order.
+    pub fn walls<'a>(&'a self) -> impl Iterator<Item = Location> + 'a {
+        (0..self.layout.walls.len())
+            .filter(move |&cell| self.layout.walls[cell])
+            .map(move |cell| self.cell_location(cell as u16))
+    }
+    pub fn is_wall(&self, location: &Location) -> bool {
+        Self::check_location_invariants(self.layout.width, self.layout.height, location).is_ok() &&
+        self.layout.is_wall(self.location_cell(location))
+    }
+    // The fewest steps between two locations going around walls, which a block needs at least as
+    // many moves as to get from one to the other. Without walls this is the manhattan distance,
+    // as it is on grids too large for a distance table, or if walls cut the two off from each other.
+    pub fn walking_distance(&self, from: &Location, to: &Location) -> usize {
+        if let Some(ref distances) = self.layout.distances {
+            let from_cell = self.location_cell(from) as usize;
+            let to_cell = self.location_cell(to) as usize;
+            let distance = distances[from_cell * self.cell_count() + to_cell];
+            if distance != u16::MAX {
+                return distance as usize;
             }
-            print!("{}\n", wall_char);
         }
-        println!("{}", horizontal_wall);
+        from.distance_to(to)
     }
     pub fn latex_print(&self) {
         let wall_char = '*';
@@ -55,17 +259,17 @@ impl World {
         let none_char = '~';
         let padding_char = '~';
 
-        let horizontal_wall = iter::repeat(format!("{}{}", wall_char, padding_char))
-            .take(self.width as usize + 2)
-            .collect::<String>();
+        let horizontal_wall = format!("{}{}", wall_char, padding_char)
+            .repeat(self.width() + 2);
 
         print!("{}\\\\{}", horizontal_wall, wall_char);
-        for y in 0..self.height {
+        for y in 0..self.layout.height {
             print!("{}{}", wall_char, padding_char);
-            for x in 0..self.width {
+            for x in 0..self.layout.width {
                 match self.get_grid_location(&Location::new(x, y)).unwrap() {
                     Entity::Agent => print!("{}", agent_char),
                     Entity::Block(block_char) => print!("{}", block_char),
+                    Entity::Wall => print!("{}", wall_char),
                     Entity::None => print!("{}", none_char),
                 }
                 print!("{}", padding_char);
@@ -76,7 +280,7 @@ impl World {
     }
 
     pub fn clone_and_move_agent(&self, direction: &Direction) -> Result<World, WorldError> {
-        let old_agent_location = self.entities.get_by_first(&Entity::Agent).unwrap();
+        let old_agent_location = self.cell_location(self.state.cells[0]);
         let new_agent_location = Location::new(old_agent_location.x +
                                                match *direction {
                                                    Direction::Left => -1,
@@ -90,47 +294,262 @@ impl World {
                                                    _ => 0,
                                                });
 
-        Self::check_location_invariants(self.width, self.height, &new_agent_location)?;
+        Self::check_location_invariants(self.layout.width, self.layout.height, &new_agent_location)?;
+        let new_agent_cell = self.location_cell(&new_agent_location);
+        if self.layout.is_wall(new_agent_cell) {
+            return Err(WorldError::WallCollisionError);
+        }
 
         let mut clone_world = self.clone();
 
-        let new_agent_location_entity = clone_world.get_grid_location(&new_agent_location).unwrap();
-        match new_agent_location_entity {
-            Entity::None => (),
-            _ => clone_world.set_entity_location(new_agent_location_entity, old_agent_location.clone()),
+        let old_agent_cell = self.state.cells[0];
+        let moved_block = self.block_index(new_agent_cell);
+        match self.layout.move_rules {
+            // Whatever is at the agent's new location swaps into its old one.
+            MoveRules::Swap => {
+                if let Some(index) = moved_block {
+                    clone_world.state.cells[index] = old_agent_cell;
+                }
+            }
+            MoveRules::Push | MoveRules::PushPull => {
+                if let Some(index) = moved_block {
+                    let pushed_location = new_agent_location.step(*direction);
+                    let pushed_cell = self.free_cell(&pushed_location)
+                        .ok_or(WorldError::BlockedPushError)?;
+                    clone_world.state.cells[index] = pushed_cell;
+                }
+                if self.layout.move_rules == MoveRules::PushPull {
+                    let behind_location = old_agent_location.step(direction.opposite());
+                    if let Some(index) = self.block_index_at(&behind_location) {
+                        clone_world.state.cells[index] = old_agent_cell;
+                    }
+                }
+            }
         }
-        clone_world.set_entity_location(Entity::Agent, new_agent_location);
+        clone_world.state.cells[0] = new_agent_cell;
 
         Ok(clone_world)
     }
+    // The world which moving the agent in this direction turns into this one, for searching backwards.
+    // Swapping the agent back from where it came from undoes a swap move, so under swap rules this
+    // is a move the opposite way. Under push rules it pulls back (or pushes back) the blocks the
+    // move moved; as the move may or may not have moved the blocks around the agent, there can be
+    // several such worlds, and this is the one where it moved the most.
+    // previous_worlds gives them all.
+    pub fn clone_and_unmove_agent(&self, direction: &Direction) -> Result<World, WorldError> {
+        if self.layout.move_rules == MoveRules::Swap {
+            return self.clone_and_move_agent(&direction.opposite());
+        }
+        let previous_agent_location = self.cell_location(self.state.cells[0])
+            .step(direction.opposite());
+        Self::check_location_invariants(self.layout.width,
+                                        self.layout.height,
+                                        &previous_agent_location)?;
+        if self.layout.is_wall(self.location_cell(&previous_agent_location)) {
+            return Err(WorldError::WallCollisionError);
+        }
+        // Otherwise the agent can only not have come from there if a block is in the way.
+        self.previous_worlds(direction).pop().ok_or(WorldError::BlockedPushError)
+    }
+    // Every world which moving the agent in this direction turns into this one, fewest blocks
+    // moved first. Under swap rules there's at most one, but under push rules the agent may or
+    // may not have pushed the block ahead of it, or pulled the one behind it.
+    pub fn previous_worlds(&self, direction: &Direction) -> Vec<World> {
+        if self.layout.move_rules == MoveRules::Swap {
+            return self.clone_and_move_agent(&direction.opposite()).into_iter().collect();
+        }
+        let agent_cell = self.state.cells[0];
+        let agent_location = self.cell_location(agent_cell);
+        let previous_agent_location = agent_location.step(direction.opposite());
+        if Self::check_location_invariants(self.layout.width,
+                                           self.layout.height,
+                                           &previous_agent_location)
+            .is_err() {
+            return Vec::new();
+        }
+        let previous_agent_cell = self.location_cell(&previous_agent_location);
+        if self.layout.is_wall(previous_agent_cell) {
+            return Vec::new();
+        }
+        let pushed_block = self.block_index_at(&agent_location.step(*direction));
+        let pulled_block = match self.layout.move_rules {
+            MoveRules::PushPull => self.block_index(previous_agent_cell),
+            _ => None,
+        };
+
+        // In order of how many blocks are put back.
+        let mut previous_worlds = Vec::new();
+        for &unpush in &[false, true] {
+            for &unpull in &[false, true] {
+                if unpush && pushed_block.is_none() || unpull && pulled_block.is_none() {
+                    continue;
+                }
+                let mut world = self.clone();
+                world.state.cells[0] = previous_agent_cell;
+                if unpush {
+                    world.state.cells[pushed_block.unwrap()] = agent_cell;
+                }
+                if unpull {
+                    let pulled_location = previous_agent_location.step(direction.opposite());
+                    match self.free_cell(&pulled_location) {
+                        Some(cell) => world.state.cells[pulled_block.unwrap()] = cell,
+                        None => continue,
+                    }
+                }
+                // The candidate must be a real world, from which the move really leads here.
+                let mut cells = world.state.cells.to_vec();
+                cells.sort();
+                cells.dedup();
+                let leads_here = |next: World| next.state == self.state;
+                if cells.len() == world.state.cells.len() &&
+                   world.clone_and_move_agent(direction).is_ok_and(leads_here) {
+                    previous_worlds.push(world);
+                }
+            }
+        }
+        previous_worlds
+    }
+    // Every world with this world's blocks and the agent in any free cell,
+    // i.e. every world which is eq_ignore_agent to this one.
+    pub fn agent_placements(&self) -> Vec<World> {
+        (0..self.cell_count())
+            .map(|cell| cell as u16)
+            .filter(|&cell| !self.state.cells[1..].contains(&cell) && !self.layout.is_wall(cell))
+            .map(|cell| {
+                let mut world = self.clone();
+                world.state.cells[0] = cell;
+                world
+            })
+            .collect()
+    }
     pub fn get_grid_location(&self, location: &Location) -> Result<Entity, WorldError> {
-        Self::check_location_invariants(self.width, self.height, location)?;
-        Ok(self.entities.get_by_second(location).map(|ent| ent.clone()).unwrap_or(Entity::None))
+        Self::check_location_invariants(self.layout.width, self.layout.height, location)?;
+        let cell = self.location_cell(location);
+        Ok(match self.state.cells.iter().position(|&entity_cell| entity_cell == cell) {
+            Some(0) => Entity::Agent,
+            Some(index) => Entity::Block(self.layout.blocks[index - 1]),
+            None if self.layout.is_wall(cell) => Entity::Wall,
+            None => Entity::None,
+        })
+    }
+    pub fn get_entity_location(&self, entity: &Entity) -> Result<Location, WorldError> {
+        self.entity_index(entity)
+            .map(|index| self.cell_location(self.state.cells[index]))
+            .ok_or(WorldError::NonExistentEntityError)
+    }
+    // The index (y * width + x) of an entity's cell, for tables indexed by position.
+    pub fn get_entity_cell(&self, entity: &Entity) -> Result<usize, WorldError> {
+        self.entity_index(entity)
+            .map(|index| self.state.cells[index] as usize)
+            .ok_or(WorldError::NonExistentEntityError)
     }
-    pub fn get_entity_location(&self, entity: &Entity) -> Result<&Location, WorldError> {
-        self.entities.get_by_first(entity).ok_or(WorldError::NonExistentEntityError)
+    pub fn cell_count(&self) -> usize {
+        self.width() * self.height()
+    }
+    // This world with every block not listed removed, e.g. to search an abstraction of it.
+    // The walls stay.
+    pub fn with_only_blocks(&self, block_chars: &[char]) -> Result<World, WorldError> {
+        let mut entities = BidirMap::new();
+        entities.insert(Entity::Agent, self.cell_location(self.state.cells[0]));
+        for &block_char in block_chars {
+            let entity = Entity::Block(block_char);
+            let location = self.get_entity_location(&entity)?;
+            entities.insert(entity, location);
+        }
+        let world = World::new(self.width(), self.height(), &entities)?;
+        Ok(World {
+            layout: Rc::new(Layout {
+                walls: self.layout.walls.clone(),
+                distances: self.layout.distances.clone(),
+                move_rules: self.layout.move_rules,
+                ..Rc::try_unwrap(world.layout).unwrap()
+            }),
+            state: world.state,
+        })
     }
     pub fn set_entity_location(&mut self, entity: Entity, location: Location) {
-        self.entities.insert(entity, location).unwrap();
+        let index = self.entity_index(&entity).unwrap();
+        self.state.cells[index] = self.location_cell(&location);
     }
     pub fn eq_ignore_agent(&self, other: &World) -> bool {
-        if self.width != other.width || self.height != other.height {
-            return false;
-        }
-        if self.entities.len() != other.entities.len() {
-            return false;
+        (Rc::ptr_eq(&self.layout, &other.layout) || self.layout == other.layout) &&
+        self.state.cells[1..] == other.state.cells[1..]
+    }
+
+    // Position of an entity within the packed state.
+    fn entity_index(&self, entity: &Entity) -> Option<usize> {
+        match *entity {
+            Entity::Agent => Some(0),
+            Entity::Block(block_char) => {
+                self.layout.blocks.binary_search(&block_char).ok().map(|index| index + 1)
+            }
+            Entity::Wall | Entity::None => None,
         }
-        self.entities
+    }
+    // Position within the packed state of the block in the cell, if there is one.
+    fn block_index(&self, cell: u16) -> Option<usize> {
+        self.state.cells[1..]
             .iter()
-            .filter(|&&(ref ent, _)| *ent != Entity::Agent)
-            .all(|&(ref ent, ref loc)| other.entities.get_by_first(ent) == Some(loc))
+            .position(|&block_cell| block_cell == cell)
+            .map(|index| index + 1)
+    }
+    fn block_index_at(&self, location: &Location) -> Option<usize> {
+        Self::check_location_invariants(self.layout.width, self.layout.height, location).ok()?;
+        self.block_index(self.location_cell(location))
+    }
+    // The location's cell, if it's in bounds and there's no wall, block or agent in it.
+    fn free_cell(&self, location: &Location) -> Option<u16> {
+        Self::check_location_invariants(self.layout.width, self.layout.height, location).ok()?;
+        let cell = self.location_cell(location);
+        if self.layout.is_wall(cell) || self.state.cells.contains(&cell) {
+            None
+        } else {
+            Some(cell)
+        }
+    }
+    fn location_cell(&self, location: &Location) -> u16 {
+        (location.y * self.layout.width + location.x) as u16
+    }
+    fn cell_location(&self, cell: u16) -> Location {
+        Location::new(cell as isize % self.layout.width,
+                      cell as isize / self.layout.width)
+    }
+
+    // Breadth first from every cell in turn, stepping between cells which aren't walls.
+    fn distance_table(&self, walls: &[bool]) -> Vec<u16> {
+        let cell_count = self.cell_count();
+        let mut distances = vec![u16::MAX; cell_count * cell_count];
+        let mut fringe = VecDeque::new();
+        for from_cell in (0..cell_count).filter(|&cell| !walls[cell]) {
+            let row = &mut distances[from_cell * cell_count..(from_cell + 1) * cell_count];
+            row[from_cell] = 0;
+            fringe.push_back(from_cell);
+            while let Some(cell) = fringe.pop_front() {
+                let location = self.cell_location(cell as u16);
+                for direction in Direction::directions_array().iter() {
+                    let next_location = location.step(*direction);
+                    if Self::check_location_invariants(self.layout.width,
+                                                       self.layout.height,
+                                                       &next_location)
+                        .is_err() {
+                        continue;
+                    }
+                    let next_cell = self.location_cell(&next_location) as usize;
+                    if !walls[next_cell] && row[next_cell] == u16::MAX {
+                        row[next_cell] = row[cell] + 1;
+                        fringe.push_back(next_cell);
+                    }
+                }
+            }
+        }
+        distances
     }
 
     fn check_location_invariants(width: isize,
                                  height: isize,
                                  location: &Location)
                                  -> Result<(), WorldError> {
-        if location.x >= width || location.x < 0 || location.y >= height as isize ||
+        if location.x >= width || location.x < 0 || location.y >= height ||
            location.y < 0 {
             return Err(WorldError::EntityOutOfBoundsError);
         }
@@ -142,12 +561,13 @@ impl World {
                               entity_starts: &BidirMap<Entity, Location>)
                               -> Result<(), WorldError> {
         let mut agent_count: u8 = 0;
-        for &(ref entity, ref location) in entity_starts.iter() {
+        for (entity, location) in entity_starts.iter() {
             match *entity {
                 Entity::Agent => agent_count += 1,
-                _ => (),
+                Entity::Block(_) => {}
+                Entity::Wall | Entity::None => return Err(WorldError::UnplaceableEntityError),
             }
-            Self::check_location_invariants(grid_width, grid_height, &location)?;
+            Self::check_location_invariants(grid_width, grid_height, location)?;
             if agent_count > 1 {
                 return Err(WorldError::InvalidNumberOfAgentsError);
             }
@@ -159,30 +579,91 @@ impl World {
         Ok(())
     }
 }
+// Draws the grid and its walls surrounded by a border of walls, as read back by the puzzle file
+// parser.
+impl fmt::Display for World {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let wall_char = '*';
+        let agent_char = '@';
+        let none_char = ' ';
+        let padding_char = ' ';
 
+        let horizontal_wall = format!("{}{}", wall_char, padding_char)
+            .repeat(self.width() + 2);
+
+        writeln!(f, "{}", horizontal_wall)?;
+        for y in 0..self.layout.height {
+            write!(f, "{}{}", wall_char, padding_char)?;
+            for x in 0..self.layout.width {
+                match self.get_grid_location(&Location::new(x, y)).unwrap() {
+                    Entity::Agent => write!(f, "{}", agent_char)?,
+                    Entity::Block(block_char) => write!(f, "{}", block_char)?,
+                    Entity::Wall => write!(f, "{}", wall_char)?,
+                    Entity::None => write!(f, "{}", none_char)?,
+                }
+                write!(f, "{}", padding_char)?;
+            }
+            writeln!(f, "{}", wall_char)?;
+        }
+        writeln!(f, "{}", horizontal_wall)
+    }
+}
+// Worlds in the same search share a layout, so only the state needs hashing.
+impl Hash for World {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.state.hash(state);
+    }
+}
 
 #[derive(Clone, PartialEq, Hash, Eq, Debug)]
 pub enum Entity {
     Agent,
     Block(char),
+    // An immovable cell of a world's layout, see World::with_walls.
+    Wall,
     None,
 }
 
-#[derive(Clone, Debug, Hash, PartialEq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub struct Location {
     x: isize,
     y: isize,
 }
 impl Location {
     pub fn new(x: isize, y: isize) -> Location {
-        Location { x: x, y: y }
+        Location { x, y }
+    }
+    pub fn x(&self) -> isize {
+        self.x
+    }
+    pub fn y(&self) -> isize {
+        self.y
     }
     pub fn distance_to(&self, other: &Location) -> usize {
         ((self.x - other.x).abs() + (self.y - other.y).abs()) as usize
     }
+    // The adjacent location in a direction, which may be out of bounds.
+    pub fn step(&self, direction: Direction) -> Location {
+        match direction {
+            Direction::Up => Location::new(self.x, self.y - 1),
+            Direction::Down => Location::new(self.x, self.y + 1),
+            Direction::Left => Location::new(self.x - 1, self.y),
+            Direction::Right => Location::new(self.x + 1, self.y),
+        }
+    }
+    // The direction of a single move from this location to an adjacent one.
+    pub fn direction_to(&self, other: &Location) -> Option<Direction> {
+        match (other.x - self.x, other.y - self.y) {
+            (0, -1) => Some(Direction::Up),
+            (0, 1) => Some(Direction::Down),
+            (-1, 0) => Some(Direction::Left),
+            (1, 0) => Some(Direction::Right),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Direction {
     Up,
     Down,
@@ -193,13 +674,183 @@ impl Direction {
     pub fn directions_array() -> [Direction; 4] {
         static DIRECTIONS: [Direction; 4] =
             [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
-        DIRECTIONS.clone()
+        DIRECTIONS
+    }
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+    // Single letter used when writing plans as text, e.g. "UULDR".
+    pub fn to_char(self) -> char {
+        match self {
+            Direction::Up => 'U',
+            Direction::Down => 'D',
+            Direction::Left => 'L',
+            Direction::Right => 'R',
+        }
+    }
+    pub fn from_char(direction_char: char) -> Option<Direction> {
+        match direction_char.to_ascii_uppercase() {
+            'U' => Some(Direction::Up),
+            'D' => Some(Direction::Down),
+            'L' => Some(Direction::Left),
+            'R' => Some(Direction::Right),
+            _ => None,
+        }
     }
 }
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum WorldError {
     EntityOutOfBoundsError,
     InvalidNumberOfAgentsError,
     NonExistentEntityError,
-}
\ No newline at end of file
+    GridTooLargeError,
+    // Only the agent and blocks can be placed in a new world. Walls are added with with_walls.
+    UnplaceableEntityError,
+    // The agent can't move into a wall, and walls can't be put on the agent or a block.
+    WallCollisionError,
+    // Under push rules, a block can't be pushed into a wall, another block or off the grid.
+    BlockedPushError,
+}
+
+#[cfg(test)]
+mod tests {
+    use bidir_map::BidirMap;
+
+    use super::{Direction, Entity, Location, MoveRules, World, WorldError};
+    use blocksworld::puzzle::Puzzle;
+
+    // The start world of a one row puzzle, under the given rules.
+    fn row(cells: &str, move_rules: MoveRules) -> World {
+        let grid = format!("{}\n* {}*\n{}",
+                           "* ".repeat(cells.len() / 2 + 2),
+                           cells,
+                           "* ".repeat(cells.len() / 2 + 2));
+        Puzzle::parse(&format!("{}\n\n{}\n", grid, grid))
+            .unwrap()
+            .with_move_rules(move_rules)
+            .start
+    }
+
+    fn assert_moves_to(world: &World, direction: Direction, expected: &World) {
+        assert_eq!(world.clone_and_move_agent(&direction).unwrap().state(),
+                   expected.state());
+    }
+
+    fn assert_blocked_push(world: &World, direction: Direction) {
+        match world.clone_and_move_agent(&direction) {
+            Err(WorldError::BlockedPushError) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn new_rejects_walls_and_empty_cells() {
+        for entity in &[Entity::Wall, Entity::None] {
+            let mut entity_starts = BidirMap::new();
+            entity_starts.insert(Entity::Agent, Location::new(0, 0));
+            entity_starts.insert(entity.clone(), Location::new(1, 0));
+            match World::new(2, 1, &entity_starts) {
+                Err(WorldError::UnplaceableEntityError) => {}
+                result => panic!("unexpected result for {:?}: {:?}", entity, result),
+            }
+        }
+    }
+
+    #[test]
+    fn pushes_blocks_into_free_cells() {
+        for &move_rules in &[MoveRules::Push, MoveRules::PushPull] {
+            assert_moves_to(&row("@ A   ", move_rules),
+                            Direction::Right,
+                            &row("  @ A ", move_rules));
+            // Into a wall, another block or off the grid, the push fails.
+            assert_blocked_push(&row("@ A * ", move_rules), Direction::Right);
+            assert_blocked_push(&row("@ A B ", move_rules), Direction::Right);
+            assert_blocked_push(&row("  B A @ ", move_rules), Direction::Left);
+            assert_blocked_push(&row("  @ A ", move_rules), Direction::Right);
+        }
+    }
+
+    #[test]
+    fn pulls_blocks_behind_under_push_pull_rules() {
+        assert_moves_to(&row("A @   ", MoveRules::PushPull),
+                        Direction::Right,
+                        &row("  A @ ", MoveRules::PushPull));
+        assert_moves_to(&row("A @   ", MoveRules::Push),
+                        Direction::Right,
+                        &row("A   @ ", MoveRules::Push));
+        // Pushing one block while pulling another moves both.
+        assert_moves_to(&row("A @ B   ", MoveRules::PushPull),
+                        Direction::Right,
+                        &row("  A @ B ", MoveRules::PushPull));
+    }
+
+    // Every world with the agent and both blocks in the open cells of a 3x3 grid with a wall.
+    fn every_world(move_rules: MoveRules) -> Vec<World> {
+        let walls = [Location::new(1, 1)];
+        let cells = (0..9)
+            .map(|cell| Location::new(cell % 3, cell / 3))
+            .filter(|location| !walls.contains(location))
+            .collect::<Vec<Location>>();
+        let mut worlds = Vec::new();
+        for agent in &cells {
+            for a in &cells {
+                for b in &cells {
+                    if agent == a || agent == b || a == b {
+                        continue;
+                    }
+                    let mut entities = BidirMap::new();
+                    entities.insert(Entity::Agent, agent.clone());
+                    entities.insert(Entity::Block('A'), a.clone());
+                    entities.insert(Entity::Block('B'), b.clone());
+                    let world = World::new(3, 3, &entities)
+                        .unwrap()
+                        .with_walls(&walls)
+                        .unwrap()
+                        .with_move_rules(move_rules);
+                    worlds.push(world);
+                }
+            }
+        }
+        worlds
+    }
+
+    #[test]
+    fn previous_worlds_undo_moves_exactly() {
+        for &move_rules in &[MoveRules::Push, MoveRules::PushPull] {
+            let worlds = every_world(move_rules);
+            for world in &worlds {
+                for direction in Direction::directions_array().iter() {
+                    let previous_worlds = world.previous_worlds(direction);
+                    // Every world they give leads here...
+                    for previous_world in &previous_worlds {
+                        assert_eq!(previous_world.clone_and_move_agent(direction).unwrap().state(),
+                                   world.state());
+                    }
+                    // ...and every world which leads here is one of them, once.
+                    for other_world in &worlds {
+                        let leads_here = other_world.clone_and_move_agent(direction)
+                            .map(|next_world| next_world.state() == world.state())
+                            .unwrap_or(false);
+                        let count = previous_worlds.iter()
+                            .filter(|previous_world| previous_world.state() == other_world.state())
+                            .count();
+                        assert_eq!(count,
+                                   leads_here as usize,
+                                   "{} rules, {:?} into\n{}from\n{}",
+                                   move_rules.name(),
+                                   direction,
+                                   world,
+                                   other_world);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,327 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use blocksworld_solver::search::{DirectionOrder, SearchOptions};
+use blocksworld_solver::world::MoveRules;
+
+pub const USAGE: &str = "\
+Usage: blocksworld_solver [OPTIONS] [PUZZLE_FILE]
+
+Solves the puzzle in PUZZLE_FILE, or the example puzzle from the README if none is given.
+A puzzle file holds a start grid and a goal grid, separated by a blank line, drawn as:
+
+    * * * * * *
+    *         *
+    * A B C @ *
+    * * * * * *
+
+Options:
+    -a, --algorithm LIST    Searchers to run, from astar, idastar, greedy, anytime, beam, bfs,
+                            bibfs, dfs, ids and optimal (counts every optimal plan, listing
+                            them with moves and showing the first with tree)
+                            (default: astar,ids,dfs,bfs)
+    -o, --output LIST       What to print for each search, from tree, moves and stats
+                            (default: tree,stats)
+    -d, --max-depth N       Don't expand nodes beyond depth N
+    -b, --node-budget N     Give up after expanding N nodes
+    -t, --timeout SECONDS   Give up after searching for SECONDS, e.g. 0.5
+    -s, --seed N            Seed the order children are generated in (default: random, printed
+                            with stats)
+    -f, --fixed-order       Generate children in a fixed order instead of a shuffled one
+    -g, --graph-search      Don't expand world states more than once
+    -r, --rules NAME        How the agent moves blocks, from swap (it swaps places with them),
+                            push (Sokoban style: it pushes them one cell ahead, unless a wall
+                            or block is in the way) and pushpull (push, also pulling the block
+                            behind it along) (default: swap)
+    -H, --heuristic NAME    Heuristic for astar, idastar, greedy, anytime, beam and optimal,
+                            from manhattan, agent (manhattan plus the agent's distance to the
+                            nearest misplaced block), pdb (pattern databases) and max (the
+                            largest of agent and pdb) (default: manhattan)
+    -w, --weight W          Multiply astar's heuristic by W, finding plans up to W times longer
+                            than optimal while expanding fewer nodes (default: 1). Also the
+                            weight anytime starts from (default: 3), lowering it until the
+                            plan is optimal or the timeout runs out
+        --beam-width N      Keep the best N nodes of each depth in beam search (default: 64)
+        --beam-max-width N  Restart beam searches which fail with double the width, until it
+                            passes N
+        --max-plans N       List at most N of optimal's plans (default: 20)
+        --pdb-cache DIR     Load pattern databases from DIR if they were saved there by an
+                            earlier run, and save newly built ones there
+        --trace FILE        Write every node expanded, generated and pruned, and every goal
+                            found, to FILE as JSON lines, for analysing searches offline
+        --dot FILE          Write the tree each search explored to FILE as Graphviz DOT, or the
+                            graph of world states with -g, highlighting the path to the goal
+        --dot-max-nodes N   Draw at most N nodes of each search in --dot's file (default: 200)
+        --state-space       Enumerate every world reachable from the start instead of searching,
+                            printing how many there are, the diameter, how many are at each
+                            distance from the goal (with the heuristic's mean estimate) and the
+                            hardest start worlds
+        --check-heuristic   Check the heuristic never overestimates, over every world which can
+                            reach the goal, instead of searching
+        --benchmark         Run every searcher over generated problems instead, towards
+                            PUZZLE_FILE's goal if given
+    -h, --help              Print this message
+
+LISTs are comma separated, or the option can be given more than once.
+Exits with status 1 if any search fails to find the goal, or gives up.";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Algorithm {
+    AStar,
+    IdaStar,
+    GreedyBestFirst,
+    Anytime,
+    Beam,
+    BreadthFirst,
+    DepthFirst,
+    IterativeDeepening,
+    Bidirectional,
+    OptimalPlans,
+}
+impl Algorithm {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Algorithm::AStar => "A* Search",
+            Algorithm::IdaStar => "IDA* Search",
+            Algorithm::GreedyBestFirst => "Greedy Best First Search",
+            Algorithm::Anytime => "Anytime A* Search",
+            Algorithm::Beam => "Beam Search",
+            Algorithm::BreadthFirst => "Breadth First Search",
+            Algorithm::DepthFirst => "Depth First Search",
+            Algorithm::IterativeDeepening => "Iterative Deepening Search",
+            Algorithm::Bidirectional => "Bidirectional Breadth First Search",
+            Algorithm::OptimalPlans => "All Optimal Plans Search",
+        }
+    }
+    // Whether the searcher is guided by the heuristic.
+    pub fn uses_heuristic(&self) -> bool {
+        match *self {
+            Algorithm::AStar |
+            Algorithm::IdaStar |
+            Algorithm::GreedyBestFirst |
+            Algorithm::Anytime |
+            Algorithm::Beam |
+            Algorithm::OptimalPlans => true,
+            Algorithm::BreadthFirst |
+            Algorithm::DepthFirst |
+            Algorithm::IterativeDeepening |
+            Algorithm::Bidirectional => false,
+        }
+    }
+}
+impl FromStr for Algorithm {
+    type Err = ();
+    fn from_str(name: &str) -> Result<Algorithm, ()> {
+        match name {
+            "astar" => Ok(Algorithm::AStar),
+            "idastar" => Ok(Algorithm::IdaStar),
+            "greedy" => Ok(Algorithm::GreedyBestFirst),
+            "anytime" => Ok(Algorithm::Anytime),
+            "beam" => Ok(Algorithm::Beam),
+            "bfs" => Ok(Algorithm::BreadthFirst),
+            "dfs" => Ok(Algorithm::DepthFirst),
+            "ids" => Ok(Algorithm::IterativeDeepening),
+            "bibfs" => Ok(Algorithm::Bidirectional),
+            "optimal" => Ok(Algorithm::OptimalPlans),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeuristicName {
+    Manhattan,
+    Agent,
+    PatternDatabase,
+    Max,
+}
+impl FromStr for HeuristicName {
+    type Err = ();
+    fn from_str(name: &str) -> Result<HeuristicName, ()> {
+        match name {
+            "manhattan" => Ok(HeuristicName::Manhattan),
+            "agent" => Ok(HeuristicName::Agent),
+            "pdb" => Ok(HeuristicName::PatternDatabase),
+            "max" => Ok(HeuristicName::Max),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Output {
+    Tree,
+    Moves,
+    Stats,
+}
+impl FromStr for Output {
+    type Err = ();
+    fn from_str(name: &str) -> Result<Output, ()> {
+        match name {
+            "tree" => Ok(Output::Tree),
+            "moves" => Ok(Output::Moves),
+            "stats" => Ok(Output::Stats),
+            _ => Err(()),
+        }
+    }
+}
+
+pub struct Args {
+    pub puzzle_path: Option<String>,
+    pub algorithms: Vec<Algorithm>,
+    pub outputs: Vec<Output>,
+    pub options: SearchOptions,
+    pub move_rules: MoveRules,
+    pub heuristic: HeuristicName,
+    pub weight: Option<f64>,
+    pub beam_width: Option<usize>,
+    pub beam_max_width: Option<usize>,
+    pub max_plans: Option<usize>,
+    pub pdb_cache: Option<String>,
+    pub trace_path: Option<String>,
+    pub dot_path: Option<String>,
+    pub dot_max_nodes: Option<usize>,
+    pub state_space: bool,
+    pub check_heuristic: bool,
+    pub benchmark: bool,
+    pub help: bool,
+}
+impl Args {
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Args, CliError> {
+        let mut parsed = Args {
+            puzzle_path: None,
+            algorithms: Vec::new(),
+            outputs: Vec::new(),
+            options: SearchOptions::default(),
+            move_rules: MoveRules::default(),
+            heuristic: HeuristicName::Manhattan,
+            weight: None,
+            beam_width: None,
+            beam_max_width: None,
+            max_plans: None,
+            pdb_cache: None,
+            trace_path: None,
+            dot_path: None,
+            dot_max_nodes: None,
+            state_space: false,
+            check_heuristic: false,
+            benchmark: false,
+            help: false,
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-a" | "--algorithm" => {
+                    let value = next_value(&mut args, &arg)?;
+                    parsed.algorithms.extend(parse_list::<Algorithm>(&arg, &value)?);
+                }
+                "-o" | "--output" => {
+                    let value = next_value(&mut args, &arg)?;
+                    parsed.outputs.extend(parse_list::<Output>(&arg, &value)?);
+                }
+                "-d" | "--max-depth" => {
+                    parsed.options.max_depth =
+                        Some(parse_value(&arg, &next_value(&mut args, &arg)?)?);
+                }
+                "-b" | "--node-budget" => {
+                    parsed.options.node_budget =
+                        Some(parse_value(&arg, &next_value(&mut args, &arg)?)?);
+                }
+                "-t" | "--timeout" => {
+                    let value = next_value(&mut args, &arg)?;
+                    let seconds: f64 = parse_value(&arg, &value)?;
+                    let timeout = Duration::try_from_secs_f64(seconds)
+                        .map_err(|_| CliError::InvalidValueError { option: arg.clone(), value })?;
+                    parsed.options.timeout = Some(timeout);
+                }
+                "-s" | "--seed" => {
+                    parsed.options.seed = Some(parse_value(&arg, &next_value(&mut args, &arg)?)?);
+                }
+                "-f" | "--fixed-order" => parsed.options.direction_order = DirectionOrder::Fixed,
+                "-g" | "--graph-search" => parsed.options.graph_search = true,
+                "-r" | "--rules" => {
+                    parsed.move_rules = parse_value(&arg, &next_value(&mut args, &arg)?)?;
+                }
+                "-H" | "--heuristic" => {
+                    parsed.heuristic = parse_value(&arg, &next_value(&mut args, &arg)?)?;
+                }
+                "-w" | "--weight" => {
+                    parsed.weight = Some(parse_value(&arg, &next_value(&mut args, &arg)?)?);
+                }
+                "--beam-width" => {
+                    parsed.beam_width = Some(parse_value(&arg, &next_value(&mut args, &arg)?)?);
+                }
+                "--beam-max-width" => {
+                    parsed.beam_max_width =
+                        Some(parse_value(&arg, &next_value(&mut args, &arg)?)?);
+                }
+                "--max-plans" => {
+                    parsed.max_plans = Some(parse_value(&arg, &next_value(&mut args, &arg)?)?);
+                }
+                "--pdb-cache" => parsed.pdb_cache = Some(next_value(&mut args, &arg)?),
+                "--trace" => parsed.trace_path = Some(next_value(&mut args, &arg)?),
+                "--dot" => parsed.dot_path = Some(next_value(&mut args, &arg)?),
+                "--dot-max-nodes" => {
+                    parsed.dot_max_nodes = Some(parse_value(&arg, &next_value(&mut args, &arg)?)?);
+                }
+                "--state-space" => parsed.state_space = true,
+                "--check-heuristic" => parsed.check_heuristic = true,
+                "--benchmark" => parsed.benchmark = true,
+                "-h" | "--help" => parsed.help = true,
+                _ if arg.starts_with('-') => return Err(CliError::UnknownOptionError(arg)),
+                _ if parsed.puzzle_path.is_none() => parsed.puzzle_path = Some(arg),
+                _ => return Err(CliError::UnexpectedArgumentError(arg)),
+            }
+        }
+
+        if parsed.algorithms.is_empty() {
+            parsed.algorithms = vec![Algorithm::AStar,
+                                     Algorithm::IterativeDeepening,
+                                     Algorithm::DepthFirst,
+                                     Algorithm::BreadthFirst];
+        }
+        if parsed.outputs.is_empty() {
+            parsed.outputs = vec![Output::Tree, Output::Stats];
+        }
+        Ok(parsed)
+    }
+}
+
+#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum CliError {
+    UnknownOptionError(String),
+    UnexpectedArgumentError(String),
+    MissingValueError(String),
+    InvalidValueError { option: String, value: String },
+}
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CliError::UnknownOptionError(ref option) => write!(f, "unknown option '{}'", option),
+            CliError::UnexpectedArgumentError(ref arg) => {
+                write!(f, "unexpected argument '{}', only one puzzle file can be given", arg)
+            }
+            CliError::MissingValueError(ref option) => write!(f, "'{}' needs a value", option),
+            CliError::InvalidValueError { ref option, ref value } => {
+                write!(f, "invalid value '{}' for '{}'", value, option)
+            }
+        }
+    }
+}
+
+fn next_value<I: Iterator<Item = String>>(args: &mut I, option: &str) -> Result<String, CliError> {
+    args.next().ok_or_else(|| CliError::MissingValueError(option.to_string()))
+}
+fn parse_value<T: FromStr>(option: &str, value: &str) -> Result<T, CliError> {
+    value.parse().map_err(|_| {
+        CliError::InvalidValueError {
+            option: option.to_string(),
+            value: value.to_string(),
+        }
+    })
+}
+fn parse_list<T: FromStr>(option: &str, value: &str) -> Result<Vec<T>, CliError> {
+    value.split(',').map(|item| parse_value(option, item.trim())).collect()
+}
@@ -0,0 +1,32 @@
+// A solver for the blocksworld puzzle: an agent moving around a grid, swapping places with
+// lettered blocks, until the blocks are arranged as in a goal grid.
+//
+// The public interface is made up of:
+// - world: the grid, its entities and the moves the agent can make, by swapping with blocks or
+//   pushing them (MoveRules).
+// - search: the searchers (A*, IDA*, greedy best first, anytime, beam, breadth first, bidirectional,
+//   depth first and iterative deepening), one which finds every optimal plan, the heuristics
+//   the informed searchers can use, deadlock detection for push rules, and the stats and observers
+//   which report what a search did.
+// - plan: the moves found by a search, which can be replayed against a start world.
+// - validator: checks move sequences from elsewhere against a start and goal world.
+// - puzzle: reads and writes start and goal worlds as text.
+// - generators: builds problems of increasing difficulty from a goal world.
+// - benchmark: runs every searcher over generated problems and prints average expanded nodes,
+//   towards the default goal or any other (benchmark_with_goal).
+// - check_admissible: checks a heuristic never overestimates, over every world of a small puzzle.
+// - StateSpace: every world reachable in a small puzzle, with its size, diameter and distances to
+//   the goal.
+extern crate bidir_map;
+
+mod blocksworld;
+
+pub use blocksworld::world;
+pub use blocksworld::search;
+pub use blocksworld::plan;
+pub use blocksworld::validator;
+pub use blocksworld::puzzle;
+pub use blocksworld::test::problem_generators as generators;
+pub use blocksworld::test::test as benchmark;
+pub use blocksworld::test::test_with_goal as benchmark_with_goal;
+pub use blocksworld::test::{check_admissible, Overestimate, StateSpace};
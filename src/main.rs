@@ -1,31 +1,389 @@
 extern crate bidir_map;
+extern crate blocksworld_solver;
 
 use bidir_map::BidirMap;
 
-mod blocksworld;
+use std::cell::RefCell;
+use std::env;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::process;
+use std::rc::Rc;
 
-use blocksworld::world::{World, Entity, Location};
-use blocksworld::search::{AStarSearcher, DepthFirstSearcher, BreadthFirstSearcher,
-                          IterativeDeepeningSearcher, Node};
-use blocksworld::test::test;
+mod cli;
+
+use blocksworld_solver::world::{World, Entity, Location};
+use blocksworld_solver::plan::Plan;
+use blocksworld_solver::puzzle::Puzzle;
+use blocksworld_solver::StateSpace;
+use blocksworld_solver::search::{AStarSearcher, DepthFirstSearcher, BreadthFirstSearcher,
+                                 IterativeDeepeningSearcher, IdaStarSearcher,
+                                 GreedyBestFirstSearcher, AnytimeSearcher, BeamSearcher,
+                                 BidirectionalSearcher, OptimalPlansSearcher, BasicNode, Node,
+                                 SearcherError, SearchStats, DirectionOrder, Heuristic,
+                                 ManhattanHeuristic, AgentHeuristic, MaxHeuristic,
+                                 PatternDatabaseBuilder, PatternDatabaseError, JsonTraceObserver,
+                                 DotTreeObserver, SearchObserver};
+use cli::{Algorithm, Args, HeuristicName, Output};
+
+// How many plans the optimal plans search lists, unless --max-plans says otherwise.
+const DEFAULT_MAX_PLANS: usize = 20;
+// How many of the worlds furthest from the goal --state-space prints.
+const HARDEST_STARTS: usize = 3;
 
 fn main() {
-    basic_test();
-    // test();
+    let mut args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, cli::USAGE);
+            process::exit(2);
+        }
+    };
+    if args.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+    let puzzle = match args.puzzle_path {
+        Some(ref path) => {
+            match Puzzle::load(path) {
+                Ok(puzzle) => puzzle,
+                Err(error) => {
+                    eprintln!("error: {}: {}", path, error);
+                    process::exit(2);
+                }
+            }
+        }
+        None => default_puzzle(),
+    };
+    let puzzle = puzzle.with_move_rules(args.move_rules);
+    if args.benchmark {
+        blocksworld_solver::benchmark_with_goal(&puzzle.goal, &args.options);
+        return;
+    }
+    // Pattern databases can take a while to build or load, so searches which aren't guided by a
+    // heuristic skip them.
+    let needs_heuristic = args.check_heuristic || args.state_space ||
+                          args.algorithms.iter().any(Algorithm::uses_heuristic);
+    let heuristic = match build_heuristic(&args, &puzzle.goal, needs_heuristic) {
+        Ok(heuristic) => heuristic,
+        Err(error) => {
+            eprintln!("error: could not build heuristic: {:?}", error);
+            process::exit(2);
+        }
+    };
+    if args.check_heuristic {
+        match blocksworld_solver::check_admissible(&puzzle.goal, &heuristic) {
+            Ok(worlds) => println!("Never overestimates, checked {} worlds", worlds),
+            Err(overestimate) => {
+                overestimate.world.pretty_print();
+                println!("Overestimates: estimated {} moves, needs {}",
+                         overestimate.estimate,
+                         overestimate.distance);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+    if args.state_space {
+        print_state_space(&puzzle, &heuristic);
+        return;
+    }
+
+    // Every searcher shares the seed, which is printed so the run can be repeated with --seed.
+    let seed = args.options.fix_seed();
+    if args.outputs.contains(&Output::Stats) &&
+       args.options.direction_order == DirectionOrder::Shuffled {
+        println!("Seed: {}", seed);
+    }
+
+    let trace = args.trace_path.as_ref().map(|path| match File::create(path) {
+        Ok(file) => Rc::new(RefCell::new(JsonTraceObserver::new(BufWriter::new(file)))),
+        Err(error) => {
+            eprintln!("error: {}: {}", path, error);
+            process::exit(2);
+        }
+    });
+    let mut dot_file = args.dot_path.as_ref().map(|path| match File::create(path) {
+        Ok(file) => BufWriter::new(file),
+        Err(error) => {
+            eprintln!("error: {}: {}", path, error);
+            process::exit(2);
+        }
+    });
+
+    let mut all_found = true;
+    for algorithm in &args.algorithms {
+        if let Some(ref trace) = trace {
+            trace.borrow_mut().start_search(algorithm.name());
+        }
+        // Each search is drawn as its own graph in the DOT file.
+        let dot = dot_file.as_ref().map(|_| {
+            let mut dot = DotTreeObserver::new()
+                .with_max_nodes(args.dot_max_nodes.unwrap_or(DotTreeObserver::DEFAULT_MAX_NODES));
+            if args.options.graph_search {
+                dot = dot.with_state_graph();
+            }
+            if algorithm.uses_heuristic() {
+                dot = dot.with_heuristic(heuristic.clone(), puzzle.goal.clone());
+            }
+            Rc::new(RefCell::new(dot))
+        });
+        let mut observers: Vec<Rc<RefCell<dyn SearchObserver>>> = Vec::new();
+        if let Some(ref trace) = trace {
+            observers.push(trace.clone());
+        }
+        if let Some(ref dot) = dot {
+            observers.push(dot.clone());
+        }
+        args.options.observer = match observers.len() {
+            0 => None,
+            1 => observers.pop(),
+            _ => Some(Rc::new(RefCell::new(observers))),
+        };
+
+        all_found &= run_searcher(*algorithm, &puzzle, &heuristic, &args, dot.as_ref());
+
+        if let (Some(dot), Some(ref mut dot_file)) = (dot, dot_file.as_mut()) {
+            if let Err(error) = dot.borrow().write_dot(dot_file, algorithm.name()) {
+                eprintln!("error: {}: {}", args.dot_path.as_ref().unwrap(), error);
+                process::exit(2);
+            }
+        }
+    }
+    args.options.observer = None;
+    if let Some(mut dot_file) = dot_file {
+        if let Err(error) = dot_file.flush() {
+            eprintln!("error: {}: {}", args.dot_path.unwrap(), error);
+            process::exit(2);
+        }
+    }
+    if let Some(trace) = trace {
+        let trace = Rc::try_unwrap(trace).unwrap().into_inner();
+        if let Err(error) = trace.finish() {
+            eprintln!("error: {}: {}", args.trace_path.unwrap(), error);
+            process::exit(2);
+        }
+    }
+    if !all_found {
+        process::exit(1);
+    }
 }
 
-fn basic_test() {
-    let mut hard_entity_start_positions = BidirMap::new();
-    hard_entity_start_positions.insert(Entity::Block('A'), Location::new(0, 3));
-    hard_entity_start_positions.insert(Entity::Block('B'), Location::new(1, 3));
-    hard_entity_start_positions.insert(Entity::Block('C'), Location::new(2, 3));
-    hard_entity_start_positions.insert(Entity::Agent, Location::new(3, 3));
+// Runs one searcher over the puzzle, printing the requested outputs.
+// Returns whether the goal was found, drawing the path to it in dot.
+fn run_searcher(algorithm: Algorithm,
+                puzzle: &Puzzle,
+                heuristic: &Rc<dyn Heuristic>,
+                args: &Args,
+                dot: Option<&Rc<RefCell<DotTreeObserver>>>)
+                -> bool {
+    let start_world = puzzle.start.clone();
+    let goal_world = puzzle.goal.clone();
+    let options = args.options.clone();
 
-    let mut easy_entity_start_positions = BidirMap::new();
-    easy_entity_start_positions.insert(Entity::Block('A'), Location::new(1, 1));
-    easy_entity_start_positions.insert(Entity::Block('B'), Location::new(1, 2));
-    easy_entity_start_positions.insert(Entity::Block('C'), Location::new(0, 3));
-    easy_entity_start_positions.insert(Entity::Agent, Location::new(2, 3));
+    println!("Beginning {}!", algorithm.name());
+    match algorithm {
+        Algorithm::AStar => {
+            let mut a_star_searcher = AStarSearcher::new(start_world, goal_world)
+                .with_options(options)
+                .with_heuristic(heuristic.clone())
+                .with_weight(args.weight.unwrap_or(1.0));
+            print_result(a_star_searcher.search(), puzzle, &args.outputs, dot)
+        }
+        Algorithm::GreedyBestFirst => {
+            let mut greedy_searcher = GreedyBestFirstSearcher::new(start_world, goal_world)
+                .with_options(options)
+                .with_heuristic(heuristic.clone());
+            print_result(greedy_searcher.search(), puzzle, &args.outputs, dot)
+        }
+        Algorithm::Anytime => {
+            let initial_weight = args.weight.unwrap_or(AnytimeSearcher::DEFAULT_INITIAL_WEIGHT);
+            let mut anytime_searcher = AnytimeSearcher::new(start_world, goal_world)
+                .with_options(options)
+                .with_heuristic(heuristic.clone())
+                .with_weights(initial_weight, AnytimeSearcher::DEFAULT_WEIGHT_STEP);
+            let result = anytime_searcher.search(|goal_node, bound| {
+                println!("Improved Plan: {} moves (suboptimality bound {})",
+                         goal_node.get_depth(),
+                         bound);
+            });
+            print_result(result, puzzle, &args.outputs, dot)
+        }
+        Algorithm::Beam => {
+            let mut beam_searcher = BeamSearcher::new(start_world, goal_world)
+                .with_options(options)
+                .with_heuristic(heuristic.clone())
+                .with_width(args.beam_width.unwrap_or(BeamSearcher::DEFAULT_WIDTH));
+            if let Some(max_width) = args.beam_max_width {
+                beam_searcher = beam_searcher.with_widening(max_width);
+            }
+            let result = beam_searcher.search();
+            if args.outputs.contains(&Output::Stats) {
+                for &(width, expanded_nodes) in beam_searcher.widths() {
+                    println!("Width {}: Expanded Nodes: {}", width, expanded_nodes);
+                }
+            }
+            print_result(result, puzzle, &args.outputs, dot)
+        }
+        Algorithm::OptimalPlans => {
+            let result = OptimalPlansSearcher::new(start_world, goal_world)
+                .with_options(options)
+                .with_heuristic(heuristic.clone())
+                .search();
+            match result {
+                Ok((optimal_plans, stats)) => {
+                    println!("Optimal Plans: {} of {} moves",
+                             optimal_plans.count(),
+                             optimal_plans.moves());
+                    let max_plans = args.max_plans.unwrap_or(DEFAULT_MAX_PLANS);
+                    let plans = optimal_plans.plans(Some(max_plans.max(1)));
+                    if let Some(dot) = dot {
+                        dot.borrow_mut().add_goal_path(&plans[0]);
+                    }
+                    for (index, goal_node) in plans.iter().take(max_plans).enumerate() {
+                        for output in &args.outputs {
+                            match *output {
+                                Output::Tree if index == 0 => goal_node.print_tree(),
+                                Output::Moves => println!("Plan: {}", Plan::from_node(goal_node)),
+                                _ => {}
+                            }
+                        }
+                    }
+                    if args.outputs.contains(&Output::Stats) {
+                        print!("{}", stats);
+                    }
+                    true
+                }
+                Err(error) => print_result::<BasicNode>(Err(error), puzzle, &args.outputs, dot),
+            }
+        }
+        Algorithm::IdaStar => {
+            let mut ida_star_searcher =
+                IdaStarSearcher::new(start_world, goal_world)
+                    .with_options(options)
+                    .with_heuristic(heuristic.clone());
+            let result = ida_star_searcher.search();
+            if args.outputs.contains(&Output::Stats) {
+                for &(threshold, expanded_nodes) in ida_star_searcher.iterations() {
+                    println!("Threshold {}: Expanded Nodes: {}", threshold, expanded_nodes);
+                }
+            }
+            print_result(result, puzzle, &args.outputs, dot)
+        }
+        Algorithm::BreadthFirst => {
+            let result =
+                BreadthFirstSearcher::new(start_world, goal_world).with_options(options).search();
+            print_result(result, puzzle, &args.outputs, dot)
+        }
+        Algorithm::DepthFirst => {
+            let result =
+                DepthFirstSearcher::new(start_world, goal_world).with_options(options).search();
+            print_result(result, puzzle, &args.outputs, dot)
+        }
+        Algorithm::IterativeDeepening => {
+            let result = IterativeDeepeningSearcher::new(start_world, goal_world)
+                .with_options(options)
+                .search();
+            print_result(result, puzzle, &args.outputs, dot)
+        }
+        Algorithm::Bidirectional => {
+            let result =
+                BidirectionalSearcher::new(start_world, goal_world).with_options(options).search();
+            print_result(result, puzzle, &args.outputs, dot)
+        }
+    }
+}
+
+fn print_result<N: Node>(result: Result<(N, SearchStats), (SearcherError, SearchStats)>,
+                         puzzle: &Puzzle,
+                         outputs: &[Output],
+                         dot: Option<&Rc<RefCell<DotTreeObserver>>>)
+                         -> bool {
+    match result {
+        Ok((goal_node, stats)) => {
+            if let Some(dot) = dot {
+                dot.borrow_mut().add_goal_path(&goal_node);
+            }
+            for output in outputs {
+                match *output {
+                    Output::Tree => goal_node.print_tree(),
+                    Output::Moves => {
+                        let plan = Plan::from_node(&goal_node);
+                        println!("Plan: {} ({} moves, reaches goal: {})",
+                                 plan,
+                                 plan.len(),
+                                 plan.reaches_goal(&puzzle.start, &puzzle.goal).unwrap());
+                    }
+                    Output::Stats => print!("{}", stats),
+                }
+            }
+            true
+        }
+        Err((error, stats)) => {
+            println!("Search failed: {:?}", error);
+            if outputs.contains(&Output::Stats) {
+                print!("{}", stats);
+            }
+            false
+        }
+    }
+}
+
+fn print_state_space(puzzle: &Puzzle, heuristic: &Rc<dyn Heuristic>) {
+    let state_space = StateSpace::enumerate(&puzzle.start, &puzzle.goal);
+    println!("Reachable Worlds: {} ({} moves between them)",
+             state_space.size(),
+             state_space.move_count());
+    match state_space.diameter() {
+        Some(diameter) => println!("Diameter: {}", diameter),
+        None => println!("Diameter: - (moves can't all be undone, and there are too many worlds)"),
+    }
+    println!("Can't Reach Goal: {}", state_space.unsolvable_count());
+    println!("{:<10}{:>12}{:>12}", "Distance", "Worlds", "Mean h");
+    let mean_estimates = state_space.mean_estimates(heuristic, &puzzle.goal);
+    for (distance, (count, mean_estimate)) in state_space.distance_distribution()
+        .iter()
+        .zip(mean_estimates)
+        .enumerate() {
+        println!("{:<10}{:>12}{:>12.2}", distance, count, mean_estimate);
+    }
+    for (distance, world) in state_space.hardest_starts(HARDEST_STARTS) {
+        println!("Hardest Start: {} moves", distance);
+        world.pretty_print();
+    }
+}
+
+// The heuristic chosen with --heuristic, or manhattan (which is never used) if it isn't needed.
+fn build_heuristic(args: &Args,
+                   goal_world: &World,
+                   needs_heuristic: bool)
+                   -> Result<Rc<dyn Heuristic>, PatternDatabaseError> {
+    if !needs_heuristic {
+        return Ok(Rc::new(ManhattanHeuristic));
+    }
+    let mut pattern_databases = PatternDatabaseBuilder::new(goal_world);
+    if let Some(ref pdb_cache) = args.pdb_cache {
+        pattern_databases = pattern_databases.with_cache_dir(pdb_cache);
+    }
+    Ok(match args.heuristic {
+        HeuristicName::Manhattan => Rc::new(ManhattanHeuristic),
+        HeuristicName::Agent => Rc::new(AgentHeuristic),
+        HeuristicName::PatternDatabase => Rc::new(pattern_databases.build_heuristic()?),
+        HeuristicName::Max => {
+            Rc::new(MaxHeuristic::new()
+                .with(AgentHeuristic)
+                .with(pattern_databases.build_heuristic()?))
+        }
+    })
+}
+
+// The example puzzle from the README.
+fn default_puzzle() -> Puzzle {
+    let mut entity_start_positions = BidirMap::new();
+    entity_start_positions.insert(Entity::Block('A'), Location::new(0, 3));
+    entity_start_positions.insert(Entity::Block('B'), Location::new(1, 3));
+    entity_start_positions.insert(Entity::Block('C'), Location::new(2, 3));
+    entity_start_positions.insert(Entity::Agent, Location::new(3, 3));
 
     let mut entity_goal_positions = BidirMap::new();
     entity_goal_positions.insert(Entity::Block('A'), Location::new(1, 1));
@@ -33,28 +391,7 @@ fn basic_test() {
     entity_goal_positions.insert(Entity::Block('C'), Location::new(1, 3));
     entity_goal_positions.insert(Entity::Agent, Location::new(3, 3));
 
-    let start_world = World::new(4, 4, &hard_entity_start_positions).unwrap();
+    let start_world = World::new(4, 4, &entity_start_positions).unwrap();
     let goal_world = World::new(4, 4, &entity_goal_positions).unwrap();
-
-    println!("Beginning A* Search!");
-    let mut a_star_searcher = AStarSearcher::new(start_world.clone(), goal_world.clone());
-    let goal_node = a_star_searcher.search().unwrap();
-    goal_node.0.print_tree();
-    println!("Expanded Nodes: {}", goal_node.1);
-    println!("Beginning Iterative Deepening Search!");
-    let mut iterative_deepening_searcher = IterativeDeepeningSearcher::new(start_world.clone(),
-                                                                           goal_world.clone());
-    let goal_node = iterative_deepening_searcher.search().unwrap();
-    goal_node.0.print_tree();
-    println!("Expanded Nodes: {}", goal_node.1);
-    println!("Beginning Depth First Search!");
-    let mut depth_searcher = DepthFirstSearcher::new(start_world.clone(), goal_world.clone());
-    let goal_node = depth_searcher.search().unwrap();
-    println!("Expanded Nodes: {}", goal_node.1);
-    goal_node.0.print_tree();
-    println!("Beginning Breadth First Search!");
-    let mut breadth_searcher = BreadthFirstSearcher::new(start_world.clone(), goal_world.clone());
-    let goal_node = breadth_searcher.search().unwrap();
-    println!("Expanded Nodes: {}", goal_node.1);
-    goal_node.0.print_tree();
-}
\ No newline at end of file
+    Puzzle::new(start_world, goal_world)
+}